@@ -0,0 +1,78 @@
+//! Serving small assets embedded in the function, e.g. with `include_bytes!`.
+//!
+//! ```ignore
+//! const SPEC: &[u8] = include_bytes!("../openapi.json");
+//! assets::static_asset(&req, SPEC, "openapi.json")
+//! ```
+use crate::{conditional, Body};
+use lambda_http::{
+    http::{header, StatusCode},
+    Error, Request, Response,
+};
+
+/// Cache policy of assets whose URL changes with their content.
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Responds with `bytes`, typed after the extension of `path`.
+///
+/// Clients revalidate on every use and get a `304 Not Modified` while their copy, identified
+/// by its ETag, is current.
+pub fn static_asset(req: &Request, bytes: &[u8], path: &str) -> Result<Response<Body>, Error> {
+    asset(req, bytes, path, "public, max-age=0, must-revalidate")
+}
+
+/// Like [`static_asset`] for fingerprinted paths, cached for a year without revalidation.
+pub fn immutable_asset(req: &Request, bytes: &[u8], path: &str) -> Result<Response<Body>, Error> {
+    asset(req, bytes, path, IMMUTABLE_CACHE_CONTROL)
+}
+
+fn asset(
+    req: &Request,
+    bytes: &[u8],
+    path: &str,
+    cache_control: &str,
+) -> Result<Response<Body>, Error> {
+    let etag = conditional::etag(bytes);
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, cache_control);
+    if conditional::if_none_match(req.headers(), &etag) {
+        return Ok(builder.status(StatusCode::NOT_MODIFIED).body(Body::Empty)?);
+    }
+
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    Ok(builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type.as_ref())
+        .body(Body::Binary(bytes.to_vec()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{immutable_asset, static_asset, IMMUTABLE_CACHE_CONTROL};
+    use lambda_http::{http::StatusCode, Request};
+
+    #[test]
+    fn it_serves_assets_with_etags() {
+        let response = static_asset(&Request::default(), b"{}", "spec/openapi.json").unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let etag = response.headers()["etag"].clone();
+
+        let mut req = Request::default();
+        req.headers_mut().insert("if-none-match", etag);
+        let response = static_asset(&req, b"{}", "openapi.json").unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = static_asset(&req, b"{\"v\":2}", "openapi.json").unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = immutable_asset(&Request::default(), b"", "favicon.ico").unwrap();
+        assert_eq!(response.headers()["content-type"], "image/x-icon");
+        assert_eq!(response.headers()["cache-control"], IMMUTABLE_CACHE_CONTROL);
+        let response = static_asset(&Request::default(), b"", "blob").unwrap();
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Several independent calls in one HTTP round trip.
+//!
+//! The request body is a JSON array of inputs, the response a `200` with an array of the same
+//! length and order. Every element is either `{"result": ...}` or `{"error": {"message": ...}}`,
+//! so a failing element does not fail the batch, and neither does an element that does not
+//! deserialize into the input type. Only bodies that are not a JSON array are rejected with
+//! `400`.
+use crate::{http, Body};
+use futures_util::{stream, StreamExt};
+use lambda_http::{http::StatusCode, Error, Request, Response};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+
+/// Runs `f` for every input of the batch in `req`, at most `max_concurrency` at a time.
+pub async fn handle_batch<I, O, T, F>(
+    req: Request,
+    max_concurrency: usize,
+    mut f: T,
+) -> Result<Response<Body>, Error>
+where
+    I: DeserializeOwned,
+    O: Serialize,
+    T: FnMut(I) -> F,
+    F: Future<Output = Result<O, Error>>,
+{
+    let inputs: Vec<Value> = match serde_json::from_slice(req.body()) {
+        Ok(inputs) => inputs,
+        Err(e) => return http::bad_request(json!({ "error": e.to_string() })),
+    };
+
+    let results: Vec<Value> = stream::iter(inputs)
+        .map(|input| {
+            let output = serde_json::from_value::<I>(input)
+                .map_err(|e| Error::from(format!("invalid input: {}", e)))
+                .map(&mut f);
+            async move {
+                let output = match output {
+                    Ok(output) => output.await,
+                    Err(e) => Err(e),
+                };
+                match output.and_then(|output| Ok(serde_json::to_value(output)?)) {
+                    Ok(result) => json!({ "result": result }),
+                    Err(e) => json!({ "error": { "message": e.to_string() } }),
+                }
+            }
+        })
+        .buffered(max_concurrency.max(1))
+        .collect()
+        .await;
+    http::from_value(StatusCode::OK, Value::Array(results))
+}
+
+/// Runs a function that only answers batches, see [`handle_batch`].
+pub async fn run_batch<I, O, T, F>(max_concurrency: usize, f: T) -> Result<(), Error>
+where
+    I: DeserializeOwned,
+    O: Serialize,
+    T: FnMut(I) -> F + Clone,
+    F: Future<Output = Result<O, Error>>,
+{
+    crate::run(move |req| handle_batch(req, max_concurrency, f.clone())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::handle_batch;
+    use crate::Body;
+    use lambda_http::{http::StatusCode, Error, Request};
+    use serde_json::json;
+
+    async fn double(n: i64) -> Result<i64, Error> {
+        match n {
+            n if n < 0 => Err(Error::from("negative input")),
+            n => Ok(n * 2),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_answers_every_element() {
+        let req = Request::new("[1, -1, 3]".into());
+        let response = handle_batch(req, 2, double).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            body,
+            json!([
+                { "result": 2 },
+                { "error": { "message": "negative input" } },
+                { "result": 6 },
+            ])
+        );
+
+        let req = Request::new(r#"[1, "two", 3]"#.into());
+        let response = handle_batch(req, 2, double).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body[0], json!({ "result": 2 }));
+        assert!(body[1]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid input: "));
+        assert_eq!(body[2], json!({ "result": 6 }));
+
+        let response = handle_batch(Request::new("{}".into()), 2, double)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,268 @@
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
+use lambda_http::Error;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Response body returned by Vercel handlers.
+///
+/// Buffered variants mirror `lambda_http::Body`, while `Stream` is sent to the client chunk by
+/// chunk as it is produced.
+#[derive(Default)]
+pub enum Body {
+    #[default]
+    Empty,
+    Text(String),
+    Binary(Vec<u8>),
+    Stream(BodyStream),
+}
+
+impl Body {
+    /// Creates a streaming body from a stream of byte chunks.
+    pub fn from_stream<S, B, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<Error> + 'static,
+    {
+        Body::Stream(BodyStream::new(stream))
+    }
+
+    /// Serializes `value` as JSON text, leaving headers such as `Content-Type` to the caller.
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Result<Body, Error> {
+        Ok(Body::Text(serde_json::to_string(value)?))
+    }
+
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Body::Stream(_))
+    }
+
+    /// The contents of a buffered body, `None` for streaming bodies, see [`Body::tee`].
+    pub fn peek(&self) -> Option<&[u8]> {
+        match self {
+            Body::Empty => Some(&[]),
+            Body::Text(t) => Some(t.as_bytes()),
+            Body::Binary(b) => Some(b),
+            Body::Stream(_) => None,
+        }
+    }
+
+    /// Whether a buffered body is larger than `limit` bytes, always `false` for streams.
+    pub fn exceeds(&self, limit: usize) -> bool {
+        self.peek().is_some_and(|bytes| bytes.len() > limit)
+    }
+
+    /// Hands every chunk to `sink` as it is sent, e.g. to log a body without draining it.
+    ///
+    /// Buffered bodies are passed to `sink` right away.
+    pub fn tee(self, mut sink: impl FnMut(&[u8]) + Send + 'static) -> Body {
+        match self {
+            Body::Stream(stream) => Body::from_stream(stream.inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    sink(chunk);
+                }
+            })),
+            body => {
+                sink(body.peek().unwrap_or_default());
+                body
+            }
+        }
+    }
+
+    /// Materializes the body, draining streaming bodies to the end.
+    ///
+    /// Fails if a streaming body already handed out chunks, or when the stream yields an error.
+    pub async fn into_bytes(self) -> Result<Bytes, Error> {
+        match self {
+            Body::Empty => Ok(Bytes::new()),
+            Body::Text(t) => Ok(Bytes::from(t)),
+            Body::Binary(b) => Ok(Bytes::from(b)),
+            Body::Stream(mut stream) => {
+                if stream.consumed {
+                    return Err(Error::from("streaming body was already consumed"));
+                }
+
+                let mut buf = BytesMut::new();
+                while let Some(chunk) = stream.next().await {
+                    buf.extend_from_slice(&chunk?);
+                }
+                Ok(buf.freeze())
+            }
+        }
+    }
+
+    /// Like [`Body::into_bytes`], additionally requiring the body to be valid UTF-8.
+    pub async fn into_string(self) -> Result<String, Error> {
+        match self {
+            Body::Text(t) => Ok(t),
+            body => Ok(String::from_utf8(body.into_bytes().await?.into())?),
+        }
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Empty => f.write_str("Empty"),
+            Body::Text(t) => f.debug_tuple("Text").field(t).finish(),
+            Body::Binary(b) => f.debug_tuple("Binary").field(b).finish(),
+            Body::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
+impl From<()> for Body {
+    fn from(_: ()) -> Self {
+        Body::Empty
+    }
+}
+
+impl From<&str> for Body {
+    fn from(s: &str) -> Self {
+        Body::Text(s.to_owned())
+    }
+}
+
+impl From<String> for Body {
+    fn from(s: String) -> Self {
+        Body::Text(s)
+    }
+}
+
+impl From<Cow<'static, str>> for Body {
+    fn from(s: Cow<'static, str>) -> Self {
+        Body::Text(s.into_owned())
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(b: &[u8]) -> Self {
+        Body::Binary(b.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(b: Vec<u8>) -> Self {
+        Body::Binary(b)
+    }
+}
+
+impl From<Cow<'static, [u8]>> for Body {
+    fn from(b: Cow<'static, [u8]>) -> Self {
+        Body::Binary(b.into_owned())
+    }
+}
+
+impl From<lambda_http::Body> for Body {
+    fn from(b: lambda_http::Body) -> Self {
+        match b {
+            lambda_http::Body::Empty => Body::Empty,
+            lambda_http::Body::Text(t) => Body::Text(t),
+            lambda_http::Body::Binary(b) => Body::Binary(b),
+        }
+    }
+}
+
+/// Serializes the value as JSON text.
+///
+/// `serde_json::Value` cannot hold NaN or infinite numbers (`json!` turns them into `null`), so
+/// this only fails for values built in ways that bypass that invariant.
+impl TryFrom<serde_json::Value> for Body {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::to_string(&value).map(Body::Text)
+    }
+}
+
+/// A stream of body chunks, see [`Body::from_stream`].
+pub struct BodyStream {
+    inner: Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>,
+    consumed: bool,
+}
+
+impl BodyStream {
+    pub fn new<S, B, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<Error> + 'static,
+    {
+        use futures_util::TryStreamExt;
+
+        BodyStream {
+            inner: Box::pin(stream.map_ok(Into::into).map_err(Into::into)),
+            consumed: false,
+        }
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.as_mut().poll_next(cx);
+        if poll.is_ready() {
+            self.consumed = true;
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Body;
+    use futures_util::{stream, StreamExt};
+    use lambda_http::Error;
+    use std::sync::{Arc, Mutex};
+
+    fn chunks() -> Body {
+        Body::from_stream(stream::iter(["he", "llo"].map(Ok::<_, Error>)))
+    }
+
+    #[tokio::test]
+    async fn it_drains_streams() {
+        assert_eq!(chunks().into_string().await.unwrap(), "hello");
+        assert_eq!(Body::from("text").into_bytes().await.unwrap(), "text");
+        assert!(Body::from(vec![0xff]).into_string().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_peeks_and_tees_without_consuming() {
+        assert_eq!(Body::from("text").peek(), Some("text".as_bytes()));
+        assert!(chunks().peek().is_none());
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let body = chunks().tee(move |chunk| sink.lock().unwrap().extend_from_slice(chunk));
+        assert_eq!(body.into_string().await.unwrap(), "hello");
+        assert_eq!(*seen.lock().unwrap(), b"hello");
+    }
+
+    #[test]
+    fn it_serializes_json_values() {
+        #[derive(serde::Serialize)]
+        struct Step {
+            index: u32,
+        }
+        let body = Body::json(&Step { index: 2 }).unwrap();
+        assert!(matches!(body, Body::Text(t) if t == r#"{"index":2}"#));
+
+        let body = Body::try_from(serde_json::json!({ "ok": true, "n": f64::NAN })).unwrap();
+        assert!(matches!(body, Body::Text(t) if t == r#"{"n":null,"ok":true}"#));
+    }
+
+    #[tokio::test]
+    async fn it_rejects_consumed_streams() {
+        let Body::Stream(mut stream) = chunks() else {
+            unreachable!()
+        };
+        stream.next().await;
+        assert!(Body::Stream(stream).into_bytes().await.is_err());
+    }
+}
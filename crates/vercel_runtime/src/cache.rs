@@ -0,0 +1,102 @@
+//! Reading the `x-vercel-cache` header set by the Vercel CDN.
+//!
+//! Useful in proxy handlers to see whether an upstream Vercel deployment answered from its
+//! cache, e.g. `CacheStatus::from_headers(upstream.headers())`.
+use lambda_http::http::HeaderMap;
+use std::{fmt, str::FromStr};
+
+pub const X_VERCEL_CACHE: &str = "x-vercel-cache";
+
+/// How the CDN served a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheStatus {
+    /// Served from the cache.
+    Hit,
+    /// Not in the cache, fetched from the origin.
+    Miss,
+    /// Served from the cache while it is revalidated in the background.
+    Stale,
+    /// The cache was skipped, e.g. for a bypass cookie or an uncacheable request.
+    Bypass,
+    /// Served from a prerendered page.
+    Prerender,
+    /// Served from the cache after an on-demand revalidation.
+    Revalidated,
+}
+
+impl CacheStatus {
+    /// Parses `x-vercel-cache`, `None` when it is absent or not a known status.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers.get(X_VERCEL_CACHE)?.to_str().ok()?.parse().ok()
+    }
+
+    /// Whether the response came out of the cache.
+    pub fn is_hit(self) -> bool {
+        matches!(
+            self,
+            CacheStatus::Hit | CacheStatus::Stale | CacheStatus::Prerender
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Stale => "STALE",
+            CacheStatus::Bypass => "BYPASS",
+            CacheStatus::Prerender => "PRERENDER",
+            CacheStatus::Revalidated => "REVALIDATED",
+        }
+    }
+}
+
+impl FromStr for CacheStatus {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        let value = value.trim();
+        [
+            CacheStatus::Hit,
+            CacheStatus::Miss,
+            CacheStatus::Stale,
+            CacheStatus::Bypass,
+            CacheStatus::Prerender,
+            CacheStatus::Revalidated,
+        ]
+        .into_iter()
+        .find(|status| status.as_str().eq_ignore_ascii_case(value))
+        .ok_or(())
+    }
+}
+
+impl fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheStatus;
+    use lambda_http::http::HeaderMap;
+
+    #[test]
+    fn it_parses_cache_status() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(CacheStatus::from_headers(&headers), None);
+
+        headers.insert("x-vercel-cache", "HIT".parse().unwrap());
+        assert_eq!(CacheStatus::from_headers(&headers), Some(CacheStatus::Hit));
+        headers.insert("x-vercel-cache", " stale".parse().unwrap());
+        assert_eq!(
+            CacheStatus::from_headers(&headers),
+            Some(CacheStatus::Stale)
+        );
+        headers.insert("x-vercel-cache", "WARM".parse().unwrap());
+        assert_eq!(CacheStatus::from_headers(&headers), None);
+
+        assert!(CacheStatus::Stale.is_hit());
+        assert!(!CacheStatus::Bypass.is_hit());
+        assert_eq!(CacheStatus::Revalidated.to_string(), "REVALIDATED");
+    }
+}
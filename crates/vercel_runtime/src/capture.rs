@@ -0,0 +1,191 @@
+use crate::body::{Body, BodyStream};
+use bytes::Bytes;
+use futures_util::Stream;
+use lambda_http::{Error, Request};
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tracing::debug;
+
+type Redactor = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Bytes kept past `max_bytes` of a streaming body so a secret crossing the limit is redacted.
+const REDACT_LOOKAHEAD: usize = 1024;
+
+/// Logs truncated request and response bodies at debug level, see
+/// [`crate::RuntimeConfig::capture_bodies`].
+#[derive(Clone)]
+pub struct BodyCapture {
+    max_bytes: usize,
+    redact: Option<Redactor>,
+}
+
+impl fmt::Debug for BodyCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyCapture")
+            .field("max_bytes", &self.max_bytes)
+            .field("redact", &self.redact.is_some())
+            .finish()
+    }
+}
+
+impl BodyCapture {
+    pub fn new(max_bytes: usize) -> Self {
+        BodyCapture {
+            max_bytes,
+            redact: None,
+        }
+    }
+
+    pub fn redact(mut self, redact: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.redact = Some(Arc::new(redact));
+        self
+    }
+
+    pub(crate) fn request(&self, req: &Request) {
+        debug!(
+            method = %req.method(),
+            uri = %req.uri(),
+            body = %self.render(req.body()),
+            "Captured request body"
+        );
+    }
+
+    /// Logs buffered bodies directly and tees streaming bodies as they are sent.
+    ///
+    /// A streaming body is logged once it ended, with its full length. Only `max_bytes` plus
+    /// [`REDACT_LOOKAHEAD`] bytes of it are kept, so redacting it is best-effort: a secret that
+    /// starts before the limit and ends past the look-ahead is not matched.
+    pub(crate) fn response(&self, body: Body) -> Body {
+        match body {
+            Body::Stream(inner) => Body::from_stream(CapturedStream {
+                inner,
+                capture: self.clone(),
+                buf: Vec::new(),
+                total: 0,
+                logged: false,
+            }),
+            body => {
+                let bytes = body.peek().unwrap_or_default();
+                debug!(body = %self.render(bytes), "Captured response body");
+                body
+            }
+        }
+    }
+
+    fn render(&self, bytes: &[u8]) -> String {
+        self.render_prefix(bytes, bytes.len())
+    }
+
+    /// Renders the first bytes of a body that is `total` bytes long.
+    fn render_prefix(&self, bytes: &[u8], total: usize) -> String {
+        // Redact before truncating so a cut cannot leave part of a secret behind
+        let text = String::from_utf8_lossy(bytes);
+        let mut text = match &self.redact {
+            Some(redact) => redact(&text),
+            None => text.into_owned(),
+        };
+        if text.len() <= self.max_bytes && total == bytes.len() {
+            return text;
+        }
+
+        let mut end = self.max_bytes.min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        format!("{}... ({} bytes total)", text, total)
+    }
+}
+
+struct CapturedStream {
+    inner: BodyStream,
+    capture: BodyCapture,
+    buf: Vec<u8>,
+    total: usize,
+    logged: bool,
+}
+
+impl CapturedStream {
+    fn rendered(&self) -> String {
+        self.capture.render_prefix(&self.buf, self.total)
+    }
+
+    fn log(&mut self) {
+        if !self.logged {
+            self.logged = true;
+            debug!(body = %self.rendered(), "Captured streaming response body");
+        }
+    }
+}
+
+/// Logs what was sent when the client went away before the stream ended.
+impl Drop for CapturedStream {
+    fn drop(&mut self) {
+        self.log();
+    }
+}
+
+impl Stream for CapturedStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let room =
+                    (self.capture.max_bytes + REDACT_LOOKAHEAD).saturating_sub(self.buf.len());
+                let kept = chunk.len().min(room);
+                self.buf.extend_from_slice(&chunk[..kept]);
+                self.total += chunk.len();
+            }
+            Poll::Ready(Some(Err(_))) | Poll::Ready(None) => self.log(),
+            _ => {}
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BodyCapture;
+    use crate::body::Body;
+    use bytes::Bytes;
+    use futures_util::StreamExt;
+
+    #[test]
+    fn it_truncates_and_redacts() {
+        let capture = BodyCapture::new(8).redact(|s| s.replace("hunter2", "***"));
+        assert_eq!(
+            capture.render(b"token=hunter2"),
+            "token=**... (13 bytes total)"
+        );
+        assert_eq!(capture.render(b"a=hunter2"), "a=***");
+        assert_eq!(
+            capture.render("ééééé".as_bytes()),
+            "éééé... (10 bytes total)"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_redacts_across_the_stream_limit() {
+        let capture = BodyCapture::new(8).redact(|s| s.replace("hunter2", "***"));
+        let chunks =
+            ["token=hu", "nter2", "&more=1"].map(|c| Ok::<_, std::io::Error>(Bytes::from(c)));
+        let Body::Stream(inner) = Body::from_stream(futures_util::stream::iter(chunks)) else {
+            unreachable!()
+        };
+        let mut stream = super::CapturedStream {
+            inner,
+            capture,
+            buf: Vec::new(),
+            total: 0,
+            logged: false,
+        };
+        while stream.next().await.is_some() {}
+        assert_eq!(stream.rendered(), "token=**... (20 bytes total)");
+    }
+}
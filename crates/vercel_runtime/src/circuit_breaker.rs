@@ -0,0 +1,280 @@
+//! Failing fast while an upstream is down, instead of waiting for it on every request.
+//!
+//! A circuit starts closed and lets every call through. After `failure_threshold` failures in a
+//! row it opens, and calls fail right away with [`CircuitError::Open`], which converts to a
+//! `503` [`AppError`]. Once `reset_timeout` passed it is half-open: a single trial call goes
+//! through, closing the circuit when it succeeds and opening it again when it fails.
+//!
+//! Circuits are keyed by upstream name and shared across the warm invocations of an instance,
+//! every instance tracks its own.
+//!
+//! ```no_run
+//! # use vercel_runtime::{circuit_breaker::CircuitBreaker, error::AppError};
+//! # use std::time::Duration;
+//! # async fn call_llm() -> Result<String, AppError> { Ok(String::new()) }
+//! # async fn execute_node() -> Result<String, AppError> {
+//! let breaker = CircuitBreaker::new("llm").reset_timeout(Duration::from_secs(10));
+//! let output = breaker
+//!     .call(|| async {
+//!         tokio::time::timeout(Duration::from_secs(5), call_llm()).await?
+//!     })
+//!     .await?;
+//! # Ok(output)
+//! # }
+//! ```
+//!
+//! Any `Err` counts as a failure, so return `Ok` for upstream answers that do not mean it is
+//! down, e.g. a `404`. Bound the call with a timeout, a hanging upstream never fails otherwise.
+use crate::error::AppError;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail right away until the reset timeout passed.
+    Open,
+    /// The next call is a trial that decides whether the circuit closes.
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    opened_at: Option<Instant>,
+    trial: bool,
+}
+
+impl Circuit {
+    fn state(&self, reset_timeout: Duration) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(at) if at.elapsed() < reset_timeout => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+}
+
+fn with_circuit<R>(upstream: &str, f: impl FnOnce(&mut Circuit) -> R) -> R {
+    static CIRCUITS: OnceLock<Mutex<HashMap<String, Circuit>>> = OnceLock::new();
+    let mut circuits = CIRCUITS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(circuits.entry(upstream.to_owned()).or_default())
+}
+
+/// The settings to call an upstream with, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    upstream: String,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+}
+
+impl CircuitBreaker {
+    /// A breaker for `upstream` that opens after 5 failures in a row, for 30 seconds.
+    pub fn new(upstream: impl Into<String>) -> Self {
+        CircuitBreaker {
+            upstream: upstream.into(),
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn failure_threshold(mut self, failures: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// How long the circuit stays open before a trial call is let through.
+    pub fn reset_timeout(mut self, timeout: Duration) -> Self {
+        self.reset_timeout = timeout;
+        self
+    }
+
+    pub fn state(&self) -> CircuitState {
+        with_circuit(&self.upstream, |circuit| circuit.state(self.reset_timeout))
+    }
+
+    /// Closes the circuit, e.g. after the upstream reported it recovered.
+    pub fn reset(&self) {
+        with_circuit(&self.upstream, |circuit| *circuit = Circuit::default());
+    }
+
+    /// Runs `call` unless the circuit is open.
+    pub async fn call<F, Fut, T, E>(&self, call: F) -> Result<T, CircuitError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let admitted = with_circuit(&self.upstream, |circuit| {
+            match circuit.state(self.reset_timeout) {
+                CircuitState::Closed => Ok(false),
+                CircuitState::HalfOpen if !circuit.trial => {
+                    circuit.trial = true;
+                    Ok(true)
+                }
+                // Another call is already the trial
+                CircuitState::HalfOpen => Err(self.reset_timeout),
+                CircuitState::Open => {
+                    let elapsed = circuit.opened_at.map_or(Duration::ZERO, |at| at.elapsed());
+                    Err(self.reset_timeout.saturating_sub(elapsed))
+                }
+            }
+        });
+        let trial = match admitted {
+            Ok(trial) => Trial {
+                breaker: self,
+                active: trial,
+            },
+            Err(retry_after) => {
+                return Err(CircuitError::Open {
+                    upstream: self.upstream.clone(),
+                    retry_after,
+                })
+            }
+        };
+
+        let result = call().await;
+        trial.finish(result.is_ok());
+        result.map_err(CircuitError::Failed)
+    }
+}
+
+/// Releases the trial of a half-open circuit when the call is dropped before it finished.
+struct Trial<'a> {
+    breaker: &'a CircuitBreaker,
+    active: bool,
+}
+
+impl Trial<'_> {
+    fn finish(mut self, succeeded: bool) {
+        let CircuitBreaker {
+            upstream,
+            failure_threshold,
+            ..
+        } = self.breaker;
+        let was_trial = std::mem::take(&mut self.active);
+        with_circuit(upstream, |circuit| {
+            circuit.trial = false;
+            if succeeded {
+                if circuit.opened_at.is_some() {
+                    info!(upstream, "Circuit closed");
+                }
+                *circuit = Circuit::default();
+                return;
+            }
+            circuit.failures = circuit.failures.saturating_add(1);
+            let opens = was_trial
+                || (circuit.opened_at.is_none() && circuit.failures >= *failure_threshold);
+            if opens {
+                warn!(upstream, failures = circuit.failures, "Circuit opened");
+                circuit.opened_at = Some(Instant::now());
+            }
+        });
+    }
+}
+
+impl Drop for Trial<'_> {
+    fn drop(&mut self) {
+        if self.active {
+            with_circuit(&self.breaker.upstream, |circuit| circuit.trial = false);
+        }
+    }
+}
+
+/// The error of [`CircuitBreaker::call`].
+#[derive(Debug)]
+pub enum CircuitError<E> {
+    /// The call was not made, the upstream is considered down.
+    Open {
+        upstream: String,
+        /// When the next trial call may be let through.
+        retry_after: Duration,
+    },
+    /// The call was made and failed.
+    Failed(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open { upstream, .. } => write!(f, "{} is unavailable", upstream),
+            CircuitError::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for CircuitError<E> {}
+
+/// An open circuit is a `503`, a failed call converts like its error.
+impl<E> From<CircuitError<E>> for AppError
+where
+    AppError: From<E>,
+{
+    fn from(error: CircuitError<E>) -> Self {
+        match error {
+            CircuitError::Open { upstream, .. } => {
+                AppError::Unavailable(format!("{} is unavailable", upstream))
+            }
+            CircuitError::Failed(error) => AppError::from(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CircuitBreaker, CircuitError, CircuitState};
+    use crate::error::AppError;
+    use lambda_http::http::StatusCode;
+    use std::time::Duration;
+
+    async fn fail(breaker: &CircuitBreaker) -> CircuitError<&'static str> {
+        breaker
+            .call(|| async { Err::<(), _>("connection refused") })
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn it_opens_and_recovers() {
+        let breaker = CircuitBreaker::new("test-upstream")
+            .failure_threshold(2)
+            .reset_timeout(Duration::from_millis(50));
+        assert!(matches!(fail(&breaker).await, CircuitError::Failed(_)));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(matches!(fail(&breaker).await, CircuitError::Failed(_)));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let mut called = false;
+        let open = breaker
+            .call(|| async {
+                called = true;
+                Ok::<_, AppError>(())
+            })
+            .await
+            .unwrap_err();
+        assert!(!called);
+        assert_eq!(
+            AppError::from(open).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // A failed trial opens the circuit again right away
+        assert!(matches!(fail(&breaker).await, CircuitError::Failed(_)));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let output = breaker.call(|| async { Ok::<_, ()>(7) }).await;
+        assert_eq!(output.unwrap(), 7);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
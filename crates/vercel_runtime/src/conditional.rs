@@ -0,0 +1,237 @@
+//! Conditional requests (RFC 9110, section 13).
+//!
+//! Optimistic concurrency for a stored resource: hand out its [`etag`] on reads, let clients
+//! send it back in `If-Match` on writes, and answer `412` when someone else wrote in between.
+//!
+//! ```ignore
+//! let state = load_state(&id).await?;
+//! let current = etag(&serde_json::to_vec(&state)?);
+//! if !req.precondition_passes(Some(&current), None) {
+//!     return http::precondition_failed("workflow state was modified");
+//! }
+//! let state = apply_update(state, &req)?;
+//! save_state(&id, &state).await?;
+//! ```
+//!
+//! The check and the write are not atomic, stores that support compare-and-swap should
+//! additionally be given `current` to compare against.
+//!
+//! Reads of resources with a modification time can be revalidated with `If-Modified-Since`
+//! instead:
+//!
+//! ```ignore
+//! let updated_at = load_updated_at(&id).await?;
+//! if let Some(not_modified) = not_modified_if(updated_at, &req) {
+//!     return Ok(not_modified);
+//! }
+//! Ok(Response::builder().last_modified(updated_at).body(render(&id).await?)?)
+//! ```
+use crate::Body;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use lambda_http::http::{header, HeaderMap, Method, StatusCode};
+use lambda_http::{Request, Response};
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// A strong entity tag derived from the representation bytes, including the quotes.
+pub fn etag(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    format!("\"{}\"", URL_SAFE_NO_PAD.encode(&digest[..16]))
+}
+
+/// The entity tags of `If-Match`, `Some(vec!["*"])` for a wildcard.
+///
+/// `None` only when the header is missing. A value that is not visible ASCII contributes no
+/// tag, so an unreadable `If-Match` matches nothing instead of skipping the precondition.
+pub fn if_match(headers: &HeaderMap) -> Option<Vec<String>> {
+    if !headers.contains_key(header::IF_MATCH) {
+        return None;
+    }
+    let mut tags = Vec::new();
+    for value in headers.get_all(header::IF_MATCH) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        tags.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned),
+        );
+    }
+    Some(tags)
+}
+
+/// Whether `If-None-Match` matches `etag`, meaning the client's copy is current.
+///
+/// Uses the weak comparison, so `W/"x"` matches `"x"`.
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim_start_matches("W/").to_owned();
+    let etag = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || opaque(tag) == etag)
+}
+
+/// The date of `If-Unmodified-Since`, `None` when it is absent or not a valid HTTP date.
+pub fn if_unmodified_since(headers: &HeaderMap) -> Option<SystemTime> {
+    let value = headers.get(header::IF_UNMODIFIED_SINCE)?.to_str().ok()?;
+    httpdate::parse_http_date(value).ok()
+}
+
+/// The date of `If-Modified-Since`, `None` when it is absent or not a valid HTTP date.
+pub fn if_modified_since(headers: &HeaderMap) -> Option<SystemTime> {
+    let value = headers.get(header::IF_MODIFIED_SINCE)?.to_str().ok()?;
+    httpdate::parse_http_date(value).ok()
+}
+
+/// HTTP dates have a resolution of one second.
+fn http_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Whether the client's copy from `If-Modified-Since` is still current.
+///
+/// Only `GET` and `HEAD` are revalidated, and the date is ignored when `If-None-Match` is
+/// present, as the entity tag is the more precise validator.
+pub fn not_modified_since(method: &Method, headers: &HeaderMap, last_modified: SystemTime) -> bool {
+    if !matches!(*method, Method::GET | Method::HEAD) || headers.contains_key(header::IF_NONE_MATCH)
+    {
+        return false;
+    }
+    if_modified_since(headers).is_some_and(|since| http_secs(last_modified) <= http_secs(since))
+}
+
+/// A `304 Not Modified` carrying `Last-Modified` when the client's copy is current, see
+/// [`not_modified_since`].
+pub fn not_modified_if(last_modified: SystemTime, req: &Request) -> Option<Response<Body>> {
+    if !not_modified_since(req.method(), req.headers(), last_modified) {
+        return None;
+    }
+    let response = Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(last_modified),
+        )
+        .body(Body::Empty)
+        .expect("static responses are valid");
+    Some(response)
+}
+
+/// Evaluates the write preconditions against the current state of the resource.
+///
+/// `etag` is `None` when the resource does not exist. `If-Match` uses the strong comparison,
+/// so weak tags never match; `If-Unmodified-Since` is only consulted without `If-Match`, and
+/// requests without either header always pass.
+pub fn precondition_passes(
+    headers: &HeaderMap,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    if let Some(tags) = if_match(headers) {
+        return match etag {
+            Some(_) if tags.iter().any(|tag| tag == "*") => true,
+            Some(etag) if !etag.starts_with("W/") => tags.iter().any(|tag| tag == etag),
+            _ => false,
+        };
+    }
+    match (if_unmodified_since(headers), last_modified) {
+        (Some(since), Some(modified)) => http_secs(modified) <= http_secs(since),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etag, not_modified_if, precondition_passes};
+    use lambda_http::http::{self, header, HeaderMap, Method};
+    use std::time::{Duration, SystemTime};
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn it_checks_if_match() {
+        let current = etag(b"v2");
+        assert_eq!(current, etag(b"v2"));
+        assert_ne!(current, etag(b"v1"));
+
+        let stale = headers(header::IF_MATCH, &etag(b"v1"));
+        assert!(!precondition_passes(&stale, Some(&current), None));
+        let list = headers(header::IF_MATCH, &format!("{}, {}", etag(b"v1"), current));
+        assert!(precondition_passes(&list, Some(&current), None));
+
+        let any = headers(header::IF_MATCH, "*");
+        assert!(precondition_passes(&any, Some(&current), None));
+        assert!(!precondition_passes(&any, None, None));
+        assert!(!precondition_passes(
+            &headers(header::IF_MATCH, "W/\"a\""),
+            Some("W/\"a\""),
+            None
+        ));
+        assert!(precondition_passes(&HeaderMap::new(), None, None));
+
+        let mut opaque = HeaderMap::new();
+        opaque.insert(
+            header::IF_MATCH,
+            header::HeaderValue::from_bytes("\"vé\"".as_bytes()).unwrap(),
+        );
+        assert!(!precondition_passes(&opaque, Some(&current), None));
+    }
+
+    #[test]
+    fn it_checks_if_unmodified_since() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let since = headers(header::IF_UNMODIFIED_SINCE, "Tue, 14 Nov 2023 22:13:20 GMT");
+        assert!(precondition_passes(&since, None, Some(modified)));
+        let later = modified + Duration::from_secs(1);
+        assert!(!precondition_passes(&since, None, Some(later)));
+        let invalid = headers(header::IF_UNMODIFIED_SINCE, "yesterday");
+        assert!(precondition_passes(&invalid, None, Some(later)));
+    }
+
+    #[test]
+    fn it_answers_not_modified_since() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let request = |method: Method, name: header::HeaderName, value: &str| {
+            http::Request::builder()
+                .method(method)
+                .header(name, value)
+                .body(lambda_http::Body::Empty)
+                .unwrap()
+        };
+        let since = "Tue, 14 Nov 2023 22:13:20 GMT";
+
+        let response = not_modified_if(
+            modified,
+            &request(Method::GET, header::IF_MODIFIED_SINCE, since),
+        )
+        .unwrap();
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers()["last-modified"], since);
+
+        let later = modified + Duration::from_secs(1);
+        let get = request(Method::GET, header::IF_MODIFIED_SINCE, since);
+        assert!(not_modified_if(later, &get).is_none());
+        let post = request(Method::POST, header::IF_MODIFIED_SINCE, since);
+        assert!(not_modified_if(modified, &post).is_none());
+        let invalid = request(Method::GET, header::IF_MODIFIED_SINCE, "yesterday");
+        assert!(not_modified_if(modified, &invalid).is_none());
+
+        let mut tagged = request(Method::GET, header::IF_MODIFIED_SINCE, since);
+        tagged
+            .headers_mut()
+            .insert(header::IF_NONE_MATCH, etag(b"v1").parse().unwrap());
+        assert!(not_modified_if(modified, &tagged).is_none());
+    }
+}
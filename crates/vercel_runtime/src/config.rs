@@ -0,0 +1,478 @@
+use crate::body::Body;
+#[cfg(feature = "debug")]
+use crate::capture::BodyCapture;
+use crate::ext::RequestExt;
+use crate::headers;
+use crate::http;
+use crate::json::{JsonErrorHook, JsonErrorResponse};
+use crate::logging::CompletionLog;
+use crate::request::HeaderLimits;
+use lambda_http::http::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode,
+};
+use lambda_http::{Error, Request, Response};
+use std::time::Duration;
+use tracing::{debug, error, Level};
+
+/// What to do with plain HTTP requests, see [`RuntimeConfig::require_https`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpsPolicy {
+    /// Answer with `403 Forbidden`.
+    Reject,
+    /// Answer with a `308 Permanent Redirect` to the `https` URL.
+    Redirect,
+}
+
+/// Runtime wide settings, passed to [`crate::run_with_config`].
+///
+/// There are deliberately no connection settings (HTTP version, keep-alive, concurrency). A
+/// function instance serves one invocation at a time and only talks HTTP/1.1 to the Lambda
+/// Runtime API on localhost, over a keep-alive client that `lambda_runtime` builds itself and
+/// does not expose. Client connections end at Vercel's edge and never reach the function, so
+/// connection setup cost lives in outgoing clients: create them once, outside the handler.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    default_response_headers: HeaderMap,
+    removed_headers: Vec<HeaderName>,
+    redacted_headers: Vec<HeaderName>,
+    pub(crate) streaming: bool,
+    completion_log: CompletionLog,
+    require_https: Option<HttpsPolicy>,
+    pub(crate) max_header_bytes: Option<HeaderLimits>,
+    max_response_bytes: Option<usize>,
+    method_override: bool,
+    default_content_type: Option<HeaderValue>,
+    pub(crate) background_concurrency: Option<usize>,
+    json_error_response: Option<JsonErrorResponse>,
+    #[cfg(feature = "debug")]
+    capture: Option<BodyCapture>,
+}
+
+impl RuntimeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Headers added to every response that does not set them itself.
+    pub fn default_response_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_response_headers = headers;
+        self
+    }
+
+    /// `Content-Type` of responses with a body that do not set one, e.g.
+    /// `application/octet-stream`.
+    ///
+    /// Unset by default, which leaves the type of such responses to the platform. Empty
+    /// bodies are not given a type, streaming bodies are.
+    pub fn default_content_type(mut self, content_type: HeaderValue) -> Self {
+        self.default_content_type = Some(content_type);
+        self
+    }
+
+    /// Headers stripped from every response, e.g. `x-powered-by`.
+    pub fn remove_default_headers(mut self, names: &[HeaderName]) -> Self {
+        self.removed_headers.extend_from_slice(names);
+        self
+    }
+
+    /// Headers logged as `<redacted:N bytes>`, in addition to [`headers::REDACTED_HEADERS`].
+    pub fn redact_headers(mut self, names: &[HeaderName]) -> Self {
+        self.redacted_headers.extend_from_slice(names);
+        self
+    }
+
+    /// Declares that the function is deployed with response streaming enabled.
+    ///
+    /// Without it, streaming bodies are buffered and sent once they complete.
+    pub fn streaming(mut self, enabled: bool) -> Self {
+        self.streaming = enabled;
+        self
+    }
+
+    /// Keeps requests that did not arrive over HTTPS away from the handler.
+    ///
+    /// Vercel terminates TLS at its edge and only forwards HTTPS traffic to production
+    /// deployments, so this only takes effect behind custom proxies and in local development.
+    /// Only `X-Forwarded-Proto` is trusted, see [`RequestExt::is_secure`], and redirects go to
+    /// the `Host` the request was sent to rather than a forwarded host.
+    pub fn require_https(mut self, policy: HttpsPolicy) -> Self {
+        self.require_https = Some(policy);
+        self
+    }
+
+    /// Answers `431 Request Header Fields Too Large` when a single header (name and value)
+    /// exceeds `per_header` bytes or all headers together exceed `total` bytes.
+    ///
+    /// The limits are checked while the event is decoded: headers past the first one that
+    /// exceeds a limit are skipped instead of copied into the request, and the handler is not
+    /// called. The raw event itself is still received in full.
+    pub fn max_header_bytes(mut self, per_header: usize, total: usize) -> Self {
+        self.max_header_bytes = Some(HeaderLimits { per_header, total });
+        self
+    }
+
+    /// Replaces buffered response bodies larger than `max` bytes with a `500` that names the
+    /// sizes, instead of leaving the platform to reject them (Vercel allows 4.5 MB).
+    ///
+    /// Streaming bodies are not checked.
+    pub fn max_response_bytes(mut self, max: usize) -> Self {
+        self.max_response_bytes = Some(max);
+        self
+    }
+
+    /// Lets `POST` requests from clients limited to `GET` and `POST`, e.g. HTML forms, stand in
+    /// for `PUT`, `PATCH` or `DELETE`.
+    ///
+    /// The method is taken from the `X-HTTP-Method-Override` header, or else from a `_method`
+    /// field of an `application/x-www-form-urlencoded` body. Requests with any other original
+    /// method, and overrides to any other method, are left untouched.
+    pub fn method_override(mut self, enabled: bool) -> Self {
+        self.method_override = enabled;
+        self
+    }
+
+    /// Caps how many tasks attached with [`crate::ResponseExt::and_then_background`] run at
+    /// the same time, all of them by default.
+    pub fn background_concurrency(mut self, limit: usize) -> Self {
+        self.background_concurrency = Some(limit);
+        self
+    }
+
+    /// Builds the response for bodies rejected by [`RequestExt::expect_json`], replacing the
+    /// built-in envelope described in [`crate::json`].
+    pub fn json_error_response(mut self, respond: JsonErrorResponse) -> Self {
+        self.json_error_response = Some(respond);
+        self
+    }
+
+    /// Picks the level of the event logged when a request completes.
+    ///
+    /// Defaults to `error` for 5xx, `warn` for 4xx and `info` otherwise.
+    pub fn log_level(
+        mut self,
+        level: impl Fn(StatusCode) -> Level + Send + Sync + 'static,
+    ) -> Self {
+        self.completion_log = CompletionLog::with_level(level);
+        self
+    }
+
+    /// Logs request and response bodies, truncated to `max_bytes`, at debug level.
+    ///
+    /// Bodies are passed through `redact` before logging when set, use it to strip secrets.
+    /// Streaming responses are logged once they ended and only their first bytes are kept, so
+    /// redacting them is best-effort for a secret that crosses `max_bytes`.
+    #[cfg(feature = "debug")]
+    pub fn capture_bodies(mut self, max_bytes: usize, redact: Option<fn(&str) -> String>) -> Self {
+        let capture = BodyCapture::new(max_bytes);
+        self.capture = Some(match redact {
+            Some(redact) => capture.redact(redact),
+            None => capture,
+        });
+        self
+    }
+
+    pub(crate) fn inspect_request(&self, request: &Request) {
+        debug!(
+            method = %request.method(),
+            uri = %request.uri(),
+            headers = ?headers::RedactedHeaders {
+                headers: request.headers(),
+                extra: &self.redacted_headers,
+            },
+            "Request"
+        );
+        #[cfg(feature = "debug")]
+        if let Some(capture) = &self.capture {
+            capture.request(request);
+        }
+    }
+
+    pub(crate) fn inspect_response(&self, body: Body) -> Body {
+        #[cfg(feature = "debug")]
+        if let Some(capture) = &self.capture {
+            return capture.response(body);
+        }
+        body
+    }
+
+    pub(crate) fn override_method(&self, request: &mut Request) {
+        if !self.method_override || request.method() != Method::POST {
+            return;
+        }
+
+        let header = request
+            .headers()
+            .get("x-http-method-override")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let field = || {
+            let is_form = request
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(';').next())
+                .is_some_and(|essence| {
+                    essence
+                        .trim()
+                        .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+                });
+            if !is_form {
+                return None;
+            }
+            form_urlencoded::parse(request.body())
+                .find(|(key, _)| key == "_method")
+                .map(|(_, value)| value.into_owned())
+        };
+        let Some(method) = header.or_else(field) else {
+            return;
+        };
+
+        let method = match method.trim().to_ascii_uppercase().as_str() {
+            "PUT" => Method::PUT,
+            "PATCH" => Method::PATCH,
+            "DELETE" => Method::DELETE,
+            other => {
+                debug!(method = other, "Ignoring unsupported method override");
+                return;
+            }
+        };
+        debug!(%method, "Overriding request method");
+        *request.method_mut() = method;
+    }
+
+    pub(crate) fn limit_response(&self, response: Response<Body>) -> Result<Response<Body>, Error> {
+        let Some(max) = self.max_response_bytes else {
+            return Ok(response);
+        };
+        if !response.body().exceeds(max) {
+            return Ok(response);
+        }
+
+        let size = response.body().peek().map_or(0, <[u8]>::len);
+        let message = format!(
+            "response body of {} bytes exceeds the limit of {} bytes",
+            size, max
+        );
+        error!("{}", message);
+        http::internal_server_error(serde_json::json!({ "error": message }))
+    }
+
+    /// Makes the configured hooks available to the request extension methods.
+    pub(crate) fn attach_hooks(&self, request: &mut Request) {
+        if let Some(respond) = self.json_error_response {
+            request.extensions_mut().insert(JsonErrorHook(respond));
+        }
+    }
+
+    pub(crate) fn log_completion(&self, status: StatusCode, latency: Duration) {
+        self.completion_log.log(status, latency);
+    }
+
+    pub(crate) fn enforce_https(&self, request: &Request) -> Option<Result<Response<Body>, Error>> {
+        let policy = self.require_https?;
+        if request.is_secure() {
+            return None;
+        }
+
+        // Forwarded hosts are client controlled, only redirect to the host that was requested
+        let host = request
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+            .or_else(|| request.uri().authority().map(|a| a.to_string()))
+            .filter(|host| !host.is_empty());
+        let location = host.map(|host| {
+            let path = request.uri().path_and_query().map_or("/", |p| p.as_str());
+            format!("https://{}{}", host, path)
+        });
+        Some(match (policy, location) {
+            (HttpsPolicy::Redirect, Some(location)) => http::permanent_redirect(&location),
+            _ => http::forbidden("HTTPS required"),
+        })
+    }
+
+    pub(crate) fn apply_content_type(&self, headers: &mut HeaderMap, body: &Body) {
+        let Some(content_type) = &self.default_content_type else {
+            return;
+        };
+        if body.peek().is_some_and(<[u8]>::is_empty) {
+            return;
+        }
+        headers
+            .entry(header::CONTENT_TYPE)
+            .or_insert_with(|| content_type.clone());
+    }
+
+    pub(crate) fn apply_default_headers(&self, headers: &mut HeaderMap) {
+        for name in &self.removed_headers {
+            headers.remove(name);
+        }
+
+        for name in self.default_response_headers.keys() {
+            if headers.contains_key(name) {
+                continue;
+            }
+            for value in self.default_response_headers.get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpsPolicy, RuntimeConfig};
+    use lambda_http::http::{self, HeaderMap, HeaderName, StatusCode};
+    use lambda_http::{Body, Request, Response};
+
+    fn request(proto: &str) -> Request {
+        http::Request::builder()
+            .uri("/api/step?n=1")
+            .header("host", "example.com")
+            .header("x-forwarded-proto", proto)
+            .body(Body::Empty)
+            .unwrap()
+    }
+
+    #[test]
+    fn it_applies_default_headers_without_clobbering() {
+        let mut defaults = HeaderMap::new();
+        defaults.insert("x-app-version", "1.2.3".parse().unwrap());
+        defaults.insert("cache-control", "no-store".parse().unwrap());
+        let config = RuntimeConfig::new()
+            .default_response_headers(defaults)
+            .remove_default_headers(&[HeaderName::from_static("x-powered-by")]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("cache-control", "max-age=60".parse().unwrap());
+        headers.insert("x-powered-by", "rust".parse().unwrap());
+        config.apply_default_headers(&mut headers);
+
+        assert_eq!(headers["x-app-version"], "1.2.3");
+        assert_eq!(headers["cache-control"], "max-age=60");
+        assert!(!headers.contains_key("x-powered-by"));
+    }
+
+    #[test]
+    fn it_overrides_post_methods_when_enabled() {
+        let post = |header: Option<&str>, form: &str| {
+            let mut builder = http::Request::builder()
+                .method("POST")
+                .header("content-type", "application/x-www-form-urlencoded");
+            if let Some(header) = header {
+                builder = builder.header("x-http-method-override", header);
+            }
+            builder.body(Body::from(form)).unwrap()
+        };
+        let method = |config: &RuntimeConfig, mut request: Request| {
+            config.override_method(&mut request);
+            request.method().clone()
+        };
+
+        let config = RuntimeConfig::new().method_override(true);
+        assert_eq!(
+            method(&config, post(Some("delete"), "")),
+            http::Method::DELETE
+        );
+        assert_eq!(
+            method(&config, post(None, "a=1&_method=PUT")),
+            http::Method::PUT
+        );
+        assert_eq!(method(&config, post(Some("GET"), "")), http::Method::POST);
+        assert_eq!(
+            method(&config, post(Some("CONNECT"), "")),
+            http::Method::POST
+        );
+        assert_eq!(
+            method(&RuntimeConfig::new(), post(Some("DELETE"), "")),
+            http::Method::POST
+        );
+
+        let get = http::Request::builder()
+            .header("x-http-method-override", "DELETE")
+            .body(Body::Empty)
+            .unwrap();
+        assert_eq!(method(&config, get), http::Method::GET);
+    }
+
+    #[test]
+    fn it_applies_the_default_content_type_to_untyped_bodies() {
+        let config = RuntimeConfig::new()
+            .default_content_type(http::HeaderValue::from_static("application/octet-stream"));
+        let content_type = |headers: HeaderMap, body: crate::Body| {
+            let mut headers = headers;
+            config.apply_content_type(&mut headers, &body);
+            headers.get("content-type").cloned()
+        };
+
+        assert_eq!(
+            content_type(HeaderMap::new(), crate::Body::from("data")).unwrap(),
+            "application/octet-stream"
+        );
+        assert!(content_type(HeaderMap::new(), crate::Body::Empty).is_none());
+        let mut typed = HeaderMap::new();
+        typed.insert("content-type", "text/csv".parse().unwrap());
+        assert_eq!(
+            content_type(typed, crate::Body::from("a,b")).unwrap(),
+            "text/csv"
+        );
+    }
+
+    #[test]
+    fn it_limits_buffered_response_sizes() {
+        let config = RuntimeConfig::new().max_response_bytes(4);
+        let small = Response::new(crate::Body::from("1234"));
+        assert_eq!(
+            config.limit_response(small).unwrap().status(),
+            StatusCode::OK
+        );
+
+        let large = Response::new(crate::Body::from("12345"));
+        let response = config.limit_response(large).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (_, stream) = crate::streaming::channel();
+        let response = config.limit_response(Response::new(stream)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn it_enforces_https() {
+        let config = RuntimeConfig::new().require_https(HttpsPolicy::Redirect);
+        assert!(config.enforce_https(&request("https")).is_none());
+
+        let response = config.enforce_https(&request("http")).unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()["location"],
+            "https://example.com/api/step?n=1"
+        );
+
+        let config = RuntimeConfig::new().require_https(HttpsPolicy::Reject);
+        let response = config.enforce_https(&request("http")).unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(RuntimeConfig::new()
+            .enforce_https(&request("http"))
+            .is_none());
+    }
+
+    #[test]
+    fn it_ignores_spoofed_forwarded_headers_for_https() {
+        let mut spoofed = request("http");
+        let headers = spoofed.headers_mut();
+        headers.insert(
+            "forwarded",
+            "proto=https;host=evil.example".parse().unwrap(),
+        );
+        headers.insert("x-forwarded-host", "evil.example".parse().unwrap());
+
+        let config = RuntimeConfig::new().require_https(HttpsPolicy::Redirect);
+        let response = config.enforce_https(&spoofed).unwrap().unwrap();
+        assert_eq!(
+            response.headers()["location"],
+            "https://example.com/api/step?n=1"
+        );
+    }
+}
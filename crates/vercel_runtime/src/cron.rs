@@ -0,0 +1,110 @@
+//! Helpers for functions triggered by [Vercel Cron Jobs](https://vercel.com/docs/cron-jobs).
+//!
+//! Vercel invokes cron jobs as a plain `GET` of the configured path and only sends two
+//! headers that identify them:
+//!
+//! - `user-agent: vercel-cron/1.0`
+//! - `authorization: Bearer <CRON_SECRET>`, when the `CRON_SECRET` env var is set on the project
+//!
+//! The schedule that fired is not sent along. To expose it, embed the project `vercel.json`
+//! and register its `crons` table once at startup:
+//!
+//! ```ignore
+//! vercel_runtime::cron::load_vercel_json(include_str!("../vercel.json")).unwrap();
+//! ```
+use crate::utils::constant_time_eq;
+use lambda_http::{http::header, Error, Request};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::OnceLock};
+
+const CRON_USER_AGENT: &str = "vercel-cron/";
+
+static SCHEDULES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Metadata of a cron triggered invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronContext {
+    /// Request path, e.g. `/api/cron`.
+    pub path: String,
+    /// The cron expression configured for `path`, if a `vercel.json` was registered.
+    pub schedule: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VercelJson {
+    #[serde(default)]
+    crons: Vec<CronEntry>,
+}
+
+#[derive(Deserialize)]
+struct CronEntry {
+    path: String,
+    schedule: String,
+}
+
+/// Registers the `crons` table of a `vercel.json` document. Can only be called once.
+pub fn load_vercel_json(vercel_json: &str) -> Result<(), Error> {
+    let config: VercelJson = serde_json::from_str(vercel_json)?;
+    let schedules = config
+        .crons
+        .into_iter()
+        .map(|c| (c.path, c.schedule))
+        .collect();
+    SCHEDULES
+        .set(schedules)
+        .map_err(|_| Error::from("cron schedules were already loaded"))
+}
+
+/// Whether the request was sent by the Vercel cron scheduler.
+pub fn is_cron(req: &Request) -> bool {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ua| ua.starts_with(CRON_USER_AGENT))
+}
+
+pub fn cron_context(req: &Request) -> Option<CronContext> {
+    if !is_cron(req) {
+        return None;
+    }
+
+    let path = req.uri().path().to_owned();
+    let schedule = SCHEDULES.get().and_then(|s| s.get(&path)).cloned();
+    Some(CronContext { path, schedule })
+}
+
+/// Checks `authorization: Bearer <secret>` in constant time.
+pub fn verify_secret(req: &Request, secret: &str) -> bool {
+    let expected = format!("Bearer {}", secret);
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|v| constant_time_eq(v.as_bytes(), expected.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cron_context, verify_secret};
+    use lambda_http::{http, Body, Request};
+
+    fn request(user_agent: &str) -> Request {
+        http::Request::builder()
+            .uri("https://example.com/api/cron")
+            .header("user-agent", user_agent)
+            .header("authorization", "Bearer s3cret")
+            .body(Body::Empty)
+            .unwrap()
+    }
+
+    #[test]
+    fn it_detects_cron_invocations() {
+        let context = cron_context(&request("vercel-cron/1.0")).unwrap();
+        assert_eq!(context.path, "/api/cron");
+        assert!(cron_context(&request("curl/8.0")).is_none());
+    }
+
+    #[test]
+    fn it_verifies_the_secret() {
+        assert!(verify_secret(&request("vercel-cron/1.0"), "s3cret"));
+        assert!(!verify_secret(&request("vercel-cron/1.0"), "other"));
+    }
+}
@@ -0,0 +1,723 @@
+//! Typed configuration from environment variables.
+//!
+//! Every field of the target struct is read from the env var of the same name in upper case,
+//! e.g. `mongo_url` from `MONGO_URL`. `Option` and `#[serde(default)]` fields may be absent,
+//! lists are comma separated. All missing and invalid variables are reported together.
+//!
+//! ```
+//! # use serde::Deserialize;
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     mongo_url: String,
+//!     #[serde(default)]
+//!     max_steps: u32,
+//!     feature_flags: Option<Vec<String>>,
+//! }
+//!
+//! # std::env::set_var("MONGO_URL", "mongodb://localhost");
+//! let config = vercel_runtime::env::cached::<Config>().unwrap();
+//! assert_eq!(config.mongo_url, "mongodb://localhost");
+//! ```
+//!
+//! Values that parse but make no sense, e.g. a URL with the wrong scheme or a limit out of
+//! range, are caught by implementing [`Validate`] and loading with [`from_env_validated`] or
+//! [`cached_validated`]:
+//!
+//! ```
+//! # use serde::Deserialize;
+//! # use vercel_runtime::env::{Validate, Validation};
+//! # #[derive(Deserialize)]
+//! # struct Config { mongo_url: String, max_steps: u32 }
+//! impl Validate for Config {
+//!     fn validate(&self, v: &mut Validation) {
+//!         v.url("mongo_url", &self.mongo_url, &["mongodb", "mongodb+srv"]);
+//!         v.range("max_steps", self.max_steps, 1..=100);
+//!     }
+//! }
+//! ```
+use serde::de::{
+    self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess,
+    SeqAccess, Visitor,
+};
+use std::{
+    any::{Any, TypeId},
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fmt,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Every problem found while reading the configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvError {
+    /// Required variables that are not set.
+    pub missing: Vec<String>,
+    /// Variables that are set but could not be parsed, with the reason.
+    pub invalid: Vec<(String, String)>,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid environment")?;
+        if !self.missing.is_empty() {
+            write!(f, ", missing: {}", self.missing.join(", "))?;
+        }
+        for (name, reason) in &self.invalid {
+            write!(f, ", {}: {}", name, reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// Deserializes `T` from the process environment.
+///
+/// Variables whose name or value is not valid Unicode are skipped, a field reading one is
+/// reported as missing.
+pub fn from_env<T: DeserializeOwned>() -> Result<T, EnvError> {
+    from_vars(unicode_vars(std::env::vars_os()))
+}
+
+/// The pairs of `vars` that are valid Unicode, `std::env::vars` panics on the others.
+fn unicode_vars(
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> impl Iterator<Item = (String, String)> {
+    vars.into_iter()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+}
+
+/// Like [`from_env`], parsing `T` only once per warm instance.
+///
+/// Failures are not cached, so a fixed environment is picked up by the next call.
+pub fn cached<T: DeserializeOwned + Send + Sync + 'static>() -> Result<Arc<T>, EnvError> {
+    cache::<T, T>(from_env::<T>)
+}
+
+/// Like [`from_env`], additionally checking the values with [`Validate`].
+///
+/// Every failed check is reported in [`EnvError::invalid`]. Checks only run once every
+/// variable parsed, since they need the whole configuration.
+pub fn from_env_validated<T: DeserializeOwned + Validate>() -> Result<T, EnvError> {
+    validated(from_env()?)
+}
+
+/// Like [`cached`], for configuration checked with [`Validate`].
+pub fn cached_validated<T>() -> Result<Arc<T>, EnvError>
+where
+    T: DeserializeOwned + Validate + Send + Sync + 'static,
+{
+    // Keyed apart from `cached::<T>`, which may have stored a value that was never checked
+    cache::<Validated<T>, T>(from_env_validated::<T>)
+}
+
+struct Validated<T>(std::marker::PhantomData<T>);
+
+fn cache<K: 'static, T: Send + Sync + 'static>(
+    load: impl FnOnce() -> Result<T, EnvError>,
+) -> Result<Arc<T>, EnvError> {
+    type Cache = Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>;
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(config) = cache
+        .lock()
+        .ok()
+        .and_then(|c| c.get(&TypeId::of::<K>()).cloned())
+    {
+        return Ok(config.downcast().expect("cache entries are keyed by type"));
+    }
+
+    let config = Arc::new(load()?);
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(TypeId::of::<K>(), config.clone());
+    }
+    Ok(config)
+}
+
+fn validated<T: Validate>(config: T) -> Result<T, EnvError> {
+    let mut validation = Validation::default();
+    config.validate(&mut validation);
+    match validation.invalid.is_empty() {
+        true => Ok(config),
+        false => Err(EnvError {
+            missing: Vec::new(),
+            invalid: validation.invalid,
+        }),
+    }
+}
+
+/// Checks on a configuration beyond what its types express, see the module docs.
+pub trait Validate {
+    fn validate(&self, v: &mut Validation);
+}
+
+/// Failed checks of a [`Validate`] implementation. Fields are named like the struct fields,
+/// and reported under their variable name.
+#[derive(Debug, Default)]
+pub struct Validation {
+    invalid: Vec<(String, String)>,
+}
+
+impl Validation {
+    /// Records a failure of `field` unless `ok` holds.
+    pub fn check(&mut self, field: &str, ok: bool, reason: impl fmt::Display) {
+        if !ok {
+            self.invalid.push((var_name(field), reason.to_string()));
+        }
+    }
+
+    /// Requires `value` to be an absolute URL with one of `schemes`, any scheme when empty.
+    pub fn url(&mut self, field: &str, value: &str, schemes: &[&str]) {
+        let Some((scheme, rest)) = value.split_once("://") else {
+            return self.check(field, false, "expected an absolute URL");
+        };
+        let valid_scheme = !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        if !valid_scheme || host.is_empty() || value.chars().any(char::is_whitespace) {
+            return self.check(field, false, "expected an absolute URL");
+        }
+        let allowed = schemes.is_empty()
+            || schemes
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(scheme));
+        self.check(
+            field,
+            allowed,
+            format_args!("expected a {} URL", schemes.join(" or ")),
+        );
+    }
+
+    /// Requires `value` to lie within `range`.
+    pub fn range<N: PartialOrd + fmt::Display>(
+        &mut self,
+        field: &str,
+        value: N,
+        range: RangeInclusive<N>,
+    ) {
+        let reason = format!(
+            "{} is outside of {}..={}",
+            value,
+            range.start(),
+            range.end()
+        );
+        self.check(field, range.contains(&value), reason);
+    }
+}
+
+pub(crate) fn from_vars<T: DeserializeOwned>(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<T, EnvError> {
+    let vars: HashMap<String, String> = vars.into_iter().collect();
+    // serde stops at the first missing field, so missing fields are filled with placeholder
+    // values and deserialization is repeated until every one of them is known
+    let mut missing: HashSet<&'static str> = HashSet::new();
+
+    loop {
+        let state = State {
+            vars: &vars,
+            missing: &missing,
+            invalid: RefCell::new(Vec::new()),
+            current: Cell::new(""),
+        };
+        let result = T::deserialize(EnvDeserializer { state: &state });
+        let current = state.current.get();
+        let mut invalid = state.invalid.into_inner();
+
+        let value = match result {
+            Ok(value) => Some(value),
+            Err(EnvDeError::Missing(field)) if missing.insert(field) => continue,
+            Err(e) => {
+                invalid.push((var_name(current), e.to_string()));
+                None
+            }
+        };
+
+        let mut missing: Vec<String> = missing.into_iter().map(var_name).collect();
+        missing.sort();
+        return match value {
+            Some(value) if missing.is_empty() && invalid.is_empty() => Ok(value),
+            _ => Err(EnvError { missing, invalid }),
+        };
+    }
+}
+
+fn var_name(field: &str) -> String {
+    field.to_ascii_uppercase()
+}
+
+struct State<'a> {
+    vars: &'a HashMap<String, String>,
+    missing: &'a HashSet<&'static str>,
+    invalid: RefCell<Vec<(String, String)>>,
+    current: Cell<&'static str>,
+}
+
+#[derive(Debug)]
+enum EnvDeError {
+    Missing(&'static str),
+    Other(String),
+}
+
+impl fmt::Display for EnvDeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvDeError::Missing(field) => write!(f, "missing {}", var_name(field)),
+            EnvDeError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for EnvDeError {}
+
+impl de::Error for EnvDeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EnvDeError::Other(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        EnvDeError::Missing(field)
+    }
+}
+
+struct EnvDeserializer<'a> {
+    state: &'a State<'a>,
+}
+
+impl<'de> de::Deserializer<'de> for EnvDeserializer<'_> {
+    type Error = EnvDeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, EnvDeError> {
+        Err(de::Error::custom("configuration has to be a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EnvDeError> {
+        let fields = fields
+            .iter()
+            .copied()
+            .filter(|f| {
+                self.state.vars.contains_key(&var_name(f)) || self.state.missing.contains(f)
+            })
+            .collect::<Vec<_>>();
+        visitor.visit_map(Fields {
+            state: self.state,
+            fields: fields.into_iter(),
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct Fields<'a> {
+    state: &'a State<'a>,
+    fields: std::vec::IntoIter<&'static str>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = EnvDeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EnvDeError> {
+        let Some(field) = self.fields.as_slice().first().copied() else {
+            return Ok(None);
+        };
+        self.state.current.set(field);
+        let key: StrDeserializer<'_, EnvDeError> = field.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, EnvDeError> {
+        let field = self.fields.next().expect("value follows its key");
+        let name = var_name(field);
+        match self.state.vars.get(&name) {
+            Some(value) => seed.deserialize(Value {
+                state: self.state,
+                name: &name,
+                value,
+            }),
+            None => seed.deserialize(Placeholder),
+        }
+    }
+}
+
+/// A variable's value, parsed into the type the field asks for.
+struct Value<'a> {
+    state: &'a State<'a>,
+    name: &'a str,
+    value: &'a str,
+}
+
+impl Value<'_> {
+    /// Records why the value is invalid and continues with a placeholder.
+    fn invalid<'de, V: Visitor<'de>>(
+        self,
+        visitor: V,
+        expected: &str,
+        fallback: impl FnOnce(Placeholder, V) -> Result<V::Value, EnvDeError>,
+    ) -> Result<V::Value, EnvDeError> {
+        self.state.invalid.borrow_mut().push((
+            self.name.to_owned(),
+            format!("expected {}, got `{}`", expected, self.value),
+        ));
+        fallback(Placeholder, visitor)
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident as $ty:ty),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+                match self.value.trim().parse::<$ty>() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => self.invalid(visitor, stringify!($ty), |p, v| p.$method(v)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value<'_> {
+    type Error = EnvDeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_str(self.value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EnvDeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        let items = self
+            .value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        visitor.visit_seq(Items {
+            state: self.state,
+            name: self.name,
+            items: items.into_iter(),
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EnvDeError> {
+        let value = self.value.trim();
+        match variants.contains(&value) {
+            true => visitor.visit_enum(value.into_deserializer()),
+            false => {
+                let expected = format!("one of {}", variants.join(", "));
+                self.invalid(visitor, &expected, |p, v| {
+                    p.deserialize_enum("", variants, v)
+                })
+            }
+        }
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool as bool,
+        deserialize_i8 => visit_i8 as i8,
+        deserialize_i16 => visit_i16 as i16,
+        deserialize_i32 => visit_i32 as i32,
+        deserialize_i64 => visit_i64 as i64,
+        deserialize_u8 => visit_u8 as u8,
+        deserialize_u16 => visit_u16 as u16,
+        deserialize_u32 => visit_u32 as u32,
+        deserialize_u64 => visit_u64 as u64,
+        deserialize_f32 => visit_f32 as f32,
+        deserialize_f64 => visit_f64 as f64,
+        deserialize_char => visit_char as char,
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct Items<'a> {
+    state: &'a State<'a>,
+    name: &'a str,
+    items: std::vec::IntoIter<&'a str>,
+}
+
+impl<'de> SeqAccess<'de> for Items<'_> {
+    type Error = EnvDeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, EnvDeError> {
+        match self.items.next() {
+            Some(value) => seed
+                .deserialize(Value {
+                    state: self.state,
+                    name: self.name,
+                    value,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Stands in for missing or invalid values so the remaining fields are still checked.
+struct Placeholder;
+
+impl<'de> de::Deserializer<'de> for Placeholder {
+    type Error = EnvDeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_str("")
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_char('\0')
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_none()
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EnvDeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(std::iter::empty::<()>()))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EnvDeError> {
+        let variant = variants.first().copied().unwrap_or_default();
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EnvDeError> {
+        self.deserialize_f64(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_vars, unicode_vars, validated, EnvError, Validate, Validation};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Safe,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        mongo_url: String,
+        api_key: String,
+        #[serde(default)]
+        max_steps: u32,
+        debug: Option<bool>,
+        mode: Mode,
+        #[serde(default)]
+        flags: Vec<String>,
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn it_reads_typed_config() {
+        let config: Config = from_vars(vars(&[
+            ("MONGO_URL", "mongodb://db"),
+            ("API_KEY", "k"),
+            ("MAX_STEPS", "12"),
+            ("MODE", "safe"),
+            ("FLAGS", "a, b"),
+        ]))
+        .unwrap();
+        assert_eq!(config.mongo_url, "mongodb://db");
+        assert_eq!(config.api_key, "k");
+        assert_eq!(config.max_steps, 12);
+        assert_eq!(config.debug, None);
+        assert_eq!(config.mode, Mode::Safe);
+        assert_eq!(config.flags, ["a", "b"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_skips_non_unicode_variables() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let config: Config = from_vars(unicode_vars([
+            ("MONGO_URL".into(), "mongodb://db".into()),
+            ("API_KEY".into(), "k".into()),
+            ("MODE".into(), "fast".into()),
+            ("LC_RAW".into(), OsString::from_vec(vec![0xff, 0xfe])),
+        ]))
+        .unwrap();
+        assert_eq!(config.mongo_url, "mongodb://db");
+
+        let error = from_vars::<Config>(unicode_vars([
+            ("MONGO_URL".into(), OsString::from_vec(vec![0xff])),
+            ("API_KEY".into(), "k".into()),
+            ("MODE".into(), "fast".into()),
+        ]))
+        .unwrap_err();
+        assert_eq!(error.missing, ["MONGO_URL"]);
+    }
+
+    #[test]
+    fn it_reports_every_problem_at_once() {
+        let error = from_vars::<Config>(vars(&[
+            ("MAX_STEPS", "many"),
+            ("DEBUG", "yes"),
+            ("MODE", "turbo"),
+        ]))
+        .unwrap_err();
+
+        assert_eq!(error.missing, ["API_KEY", "MONGO_URL"]);
+        let invalid: Vec<_> = error
+            .invalid
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(invalid, ["MAX_STEPS", "DEBUG", "MODE"]);
+        assert!(error.to_string().contains("missing: API_KEY, MONGO_URL"));
+        assert_ne!(error, EnvError::default());
+    }
+
+    impl Validate for Config {
+        fn validate(&self, v: &mut Validation) {
+            v.url("mongo_url", &self.mongo_url, &["mongodb", "mongodb+srv"]);
+            v.range("max_steps", self.max_steps, 1..=100);
+            v.check("api_key", !self.api_key.is_empty(), "must not be empty");
+        }
+    }
+
+    #[test]
+    fn it_reports_every_failed_check() {
+        let config: Config = from_vars(vars(&[
+            ("MONGO_URL", "postgres://db"),
+            ("API_KEY", ""),
+            ("MODE", "safe"),
+        ]))
+        .unwrap();
+        let error = validated(config).unwrap_err();
+        assert_eq!(
+            error.invalid,
+            [
+                (
+                    "MONGO_URL".to_owned(),
+                    "expected a mongodb or mongodb+srv URL".to_owned()
+                ),
+                ("MAX_STEPS".to_owned(), "0 is outside of 1..=100".to_owned()),
+                ("API_KEY".to_owned(), "must not be empty".to_owned()),
+            ]
+        );
+
+        let config: Config = from_vars(vars(&[
+            (
+                "MONGO_URL",
+                "mongodb+srv://user:pw@cluster0.example.net/app",
+            ),
+            ("API_KEY", "k"),
+            ("MAX_STEPS", "10"),
+            ("MODE", "fast"),
+        ]))
+        .unwrap();
+        assert!(validated(config).is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+//! A handler error type that answers with a fitting status instead of failing the invocation.
+//!
+//! Return `Result<_, AppError>` from the handler logic and let `?` convert the errors of the
+//! libraries it calls. [`IntoResponse`] for `Result` turns either side into the response:
+//!
+//! ```no_run
+//! # use vercel_runtime::{error::AppError, run, Error, Request, StatusCode};
+//! async fn handle(req: Request) -> Result<(StatusCode, serde_json::Value), AppError> {
+//!     let input: serde_json::Value = serde_json::from_slice(req.body())?;
+//!     Ok((StatusCode::OK, input))
+//! }
+//!
+//! # async fn example() -> Result<(), Error> {
+//! run(|req| async { Ok(handle(req).await) }).await
+//! # }
+//! ```
+//!
+//! Add `From` impls for the errors of other clients, e.g. a database driver, in the same way.
+use crate::{json::JsonError, Body, IntoResponse};
+use lambda_http::http::StatusCode;
+use lambda_http::Response;
+use std::fmt;
+use tracing::error;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// `400`, the request is malformed.
+    BadRequest(String),
+    /// `401`, credentials are missing or wrong.
+    Unauthorized(String),
+    /// `403`, the caller may not do this.
+    Forbidden(String),
+    /// `404`
+    NotFound(String),
+    /// `413`, the request body is too large.
+    PayloadTooLarge(String),
+    /// `502`, a service the handler depends on failed.
+    Upstream(String),
+    /// `503`, a service the handler depends on is known to be down, see
+    /// [`crate::circuit_breaker`].
+    Unavailable(String),
+    /// `504`, an operation ran out of time.
+    Timeout(String),
+    /// `500`, logged in full but answered with a generic message.
+    Internal(crate::Error),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Wraps any other error as [`AppError::Internal`].
+    pub fn internal(error: impl Into<crate::Error>) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::Upstream(message)
+            | AppError::Unavailable(message)
+            | AppError::Timeout(message) => f.write_str(message),
+            AppError::Internal(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// `{"error": "<message>"}` with the status of the variant.
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response<Body> {
+        let status = self.status();
+        let message = match self {
+            AppError::Internal(error) => {
+                error!("Handler failed: {}", error);
+                "internal error".to_owned()
+            }
+            error => error.to_string(),
+        };
+        (status, serde_json::json!({ "error": message })).into_response()
+    }
+}
+
+/// Syntax and data errors are the client's fault, I/O errors are not.
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        match error.classify() {
+            serde_json::error::Category::Io => AppError::internal(error),
+            _ => AppError::BadRequest(error.to_string()),
+        }
+    }
+}
+
+impl From<JsonError> for AppError {
+    fn from(error: JsonError) -> Self {
+        AppError::BadRequest(error.message)
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for AppError {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        AppError::Timeout("operation timed out".to_owned())
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for AppError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.is_timeout() {
+            true => AppError::Timeout(error.to_string()),
+            false => AppError::Upstream(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::Error> for AppError {
+    fn from(error: crate::Error) -> Self {
+        AppError::Internal(error)
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::internal(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::internal(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppError;
+    use crate::{Body, IntoResponse};
+    use lambda_http::http::StatusCode;
+    use std::time::Duration;
+
+    fn parse(body: &str) -> Result<serde_json::Value, AppError> {
+        Ok(serde_json::from_str(body)?)
+    }
+
+    #[tokio::test]
+    async fn it_maps_errors_to_statuses() {
+        let response = parse("{").unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            AppError::from(elapsed).status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+
+        let response = AppError::from("connection string leaked").into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        assert_eq!(body, r#"{"error":"internal error"}"#);
+
+        let too_large = AppError::PayloadTooLarge("upload exceeds 4 MB".to_owned());
+        assert_eq!(
+            too_large.into_response().status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
@@ -0,0 +1,585 @@
+use crate::body::Body;
+use crate::cache::CacheStatus;
+use crate::conditional;
+use crate::cron::{self, CronContext};
+use crate::forwarded::{self, ForwardedElement};
+use crate::headers;
+use crate::invocation::{Disconnected, Invocation};
+use crate::json;
+use crate::json_stream::{self, JsonSeqRecords};
+use crate::lifecycle;
+use crate::merge_patch;
+use crate::pagination;
+use crate::parsed_body::{self, ParsedBody};
+use crate::protection::{self, ProtectionBypass};
+use crate::query;
+use crate::routing::{MatchedRoute, PathSegments};
+use crate::trace::TraceContext;
+use crate::vercel_id::VercelId;
+use lambda_http::http::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    response::Builder,
+    StatusCode,
+};
+use lambda_http::{Error, Request, Response};
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime};
+use tokio_util::sync::CancellationToken;
+
+/// Vercel specific extensions for [`Request`].
+pub trait RequestExt {
+    /// Metadata of the invocation this request belongs to, if it was decoded by the runtime.
+    fn invocation(&self) -> Option<&Invocation>;
+
+    /// A token that is cancelled shortly before the invocation hits its hard timeout.
+    ///
+    /// Pass it into long-running operations so they can wind down and persist partial state
+    /// instead of being killed. Requests that were not decoded by the runtime get a token that
+    /// is never cancelled.
+    fn deadline_token(&self) -> CancellationToken;
+
+    /// A future for `tokio::select!` that resolves when the client disconnects.
+    ///
+    /// Disconnects are not observable on Vercel, so it never resolves there, see
+    /// [`Disconnected`]. Combine it with [`RequestExt::deadline_token`] to also stop on timeouts.
+    fn disconnected(&self) -> Disconnected {
+        Disconnected::default()
+    }
+
+    /// W3C trace context of the invocation, see [`crate::trace`].
+    fn trace_context(&self) -> Option<&TraceContext>;
+
+    /// Route template that matched when dispatched by [`crate::bundled_api`], e.g.
+    /// `/api/step-v4/[step]`.
+    ///
+    /// Use it instead of the concrete path to label metrics without blowing up cardinality.
+    /// `None` for requests that did not go through the bundled router.
+    fn matched_route(&self) -> Option<&str>;
+
+    /// Non-empty segments of the request path, percent-decoded, for handlers that route by
+    /// hand.
+    ///
+    /// The path is split on `/` before decoding, so an encoded `%2F` stays part of its
+    /// segment: `/files/a%2Fb` yields `files` and `a/b`. Repeated, leading and trailing
+    /// slashes produce no empty segments, and invalid UTF-8 is replaced with `U+FFFD`.
+    fn path_segments(&self) -> PathSegments<'_>;
+
+    /// When the runtime decoded the invocation event.
+    ///
+    /// Monotonic, so latencies computed from it are not affected by wall-clock changes.
+    /// Requests that were not decoded by the runtime report the time of the call.
+    fn received_at(&self) -> Instant;
+
+    /// Time since [`RequestExt::received_at`].
+    fn elapsed(&self) -> Duration {
+        self.received_at().elapsed()
+    }
+
+    /// Registers cleanup that the runtime awaits after the response has been sent.
+    ///
+    /// Finalizers run whether the handler succeeded, failed or panicked, last registered first.
+    /// Outside of the runtime (e.g. in tests) they are never run.
+    fn on_finish(&self, finalizer: impl Future<Output = ()> + Send + 'static) {
+        lifecycle::on_finish(finalizer);
+    }
+
+    /// Registers work that only runs once the response was delivered, and is dropped when the
+    /// handler fails. Runs before the [`RequestExt::on_finish`] finalizers, see
+    /// [`crate::lifecycle`].
+    fn wait_until_after_response(&self, task: impl Future<Output = ()> + Send + 'static) {
+        lifecycle::wait_until_after_response(task);
+    }
+
+    /// Whether the request carries an `x-vercel-protection-bypass` token at all.
+    fn has_protection_bypass(&self) -> bool;
+
+    /// Checks the `x-vercel-protection-bypass` token against `expected` in constant time.
+    fn protection_bypass(&self, expected: &str) -> ProtectionBypass;
+
+    /// Shorthand for `protection_bypass(expected).is_valid()`.
+    fn verify_protection_bypass(&self, expected: &str) -> bool {
+        self.protection_bypass(expected).is_valid()
+    }
+
+    /// Proxy hops from the `Forwarded` header, synthesized from `X-Forwarded-*` when absent.
+    ///
+    /// Client-controlled on Vercel, see [`forwarded::forwarded`].
+    fn forwarded(&self) -> Vec<ForwardedElement>;
+
+    /// Address of the client that made the request, see [`forwarded::client_ip`] for which
+    /// headers are trusted.
+    fn client_ip(&self) -> Option<IpAddr>;
+
+    /// Scheme the client used, from `Forwarded`/`X-Forwarded-Proto`, defaulting to `https`.
+    fn scheme(&self) -> String;
+
+    /// Whether the client used HTTPS, according to `X-Forwarded-Proto`, see
+    /// [`forwarded::is_secure`].
+    fn is_secure(&self) -> bool;
+
+    /// Whether the client sent `Expect: 100-continue`.
+    ///
+    /// Nothing needs to be answered: Vercel's edge handles the interim `100 Continue` and only
+    /// invokes the function once the whole body arrived, so the handler cannot send one itself.
+    /// Useful to spot clients that upload large bodies.
+    fn expects_continue(&self) -> bool;
+
+    /// Whether the client accepts the content coding `encoding`, honoring q-values, see
+    /// [`headers::accepts_encoding`].
+    ///
+    /// Lets handlers serve a precompressed variant and set `Content-Encoding` themselves.
+    fn accepts_encoding(&self, encoding: &str) -> bool;
+
+    /// Whether this is a prefetch that nobody may look at, see [`headers::is_prefetch`].
+    ///
+    /// Lets handlers answer with a cheap placeholder or `204 No Content` instead of running a
+    /// whole workflow for a speculative navigation. Add `Vary` on the checked header when the
+    /// response is cacheable.
+    fn is_prefetch(&self) -> bool;
+
+    /// Host the client used, falling back to the `VERCEL_URL` deployment URL.
+    fn host(&self) -> Option<String>;
+
+    /// `scheme://host` the client used, see [`forwarded::base_url`].
+    fn base_url(&self) -> Option<String>;
+
+    /// Builds `scheme://host/path` for redirects and callback URLs.
+    ///
+    /// Add parameters with a [`crate::query::QueryBuilder`] rather than by concatenating.
+    fn absolute_url(&self, path: &str) -> Option<String>;
+
+    /// Deserializes the query string into `T`, see [`crate::query`] for how lists are read.
+    fn query<T: DeserializeOwned>(&self) -> Result<T, Error>;
+
+    /// The `cursor` query parameter of a paginated list, see [`crate::pagination`].
+    fn cursor(&self) -> Option<String>;
+
+    /// The `limit` query parameter, clamped to [`pagination::MAX_LIMIT`] and defaulting to
+    /// [`pagination::DEFAULT_LIMIT`].
+    fn limit(&self) -> usize;
+
+    /// Parses a JSON body, or returns the `400` response to send back, see [`crate::json`].
+    ///
+    /// ```ignore
+    /// let input: Input = match req.expect_json() {
+    ///     Ok(input) => input,
+    ///     Err(response) => return Ok(response),
+    /// };
+    /// ```
+    #[allow(clippy::result_large_err)]
+    fn expect_json<T: DeserializeOwned>(&self) -> Result<T, Response<Body>>;
+
+    /// Parses the body as JSON, form or multipart depending on the `Content-Type`, see
+    /// [`ParsedBody`]. Unknown content types are returned as [`ParsedBody::Raw`].
+    fn body_any(&self) -> Result<ParsedBody, Error>;
+
+    /// Parses a JSON Merge Patch body, see [`crate::merge_patch::merge_patch`].
+    ///
+    /// Fails unless the content type is `application/merge-patch+json`.
+    fn json_merge_patch(&self) -> Result<serde_json::Value, Error>;
+
+    /// Parses a lenient JSON5 body, accepting comments, trailing commas and unquoted keys.
+    ///
+    /// Only meant for clients that cannot send strict JSON, `RequestPayloadExt::payload` stays
+    /// strict.
+    #[cfg(feature = "json5")]
+    fn json5<T: DeserializeOwned>(&self) -> Result<T, Error>;
+
+    /// Parses an `application/json-seq` (RFC 7464) body record by record.
+    fn json_seq_stream<T: DeserializeOwned>(&self) -> JsonSeqRecords<'_, T>;
+
+    /// The `x-vercel-cache` status of the request, see [`crate::cache`] for upstream responses.
+    fn cache_status(&self) -> Option<CacheStatus>;
+
+    /// The declared `Content-Length`.
+    ///
+    /// Requests decoded by the runtime whose body does not have the declared size are answered
+    /// with `400` before they reach the handler, so it matches [`Request::body`] there.
+    fn content_length(&self) -> Option<u64>;
+
+    /// The parsed `x-vercel-id`, see [`crate::vercel_id`] for the regions it carries.
+    fn vercel_id(&self) -> Option<VercelId>;
+
+    /// The date of `If-Modified-Since`, `None` when absent or invalid, which the spec says to
+    /// ignore. See [`crate::conditional::not_modified_if`] to answer `304`.
+    fn if_modified_since(&self) -> Option<SystemTime>;
+
+    /// The date of `If-Unmodified-Since`, `None` when absent or invalid.
+    fn if_unmodified_since(&self) -> Option<SystemTime>;
+
+    /// Whether the `If-Match` / `If-Unmodified-Since` preconditions hold for the current state
+    /// of the resource, see [`crate::conditional::precondition_passes`].
+    fn precondition_passes(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool;
+
+    /// Cron metadata when the request was sent by the Vercel cron scheduler, see [`crate::cron`].
+    fn cron_context(&self) -> Option<CronContext>;
+
+    /// Checks the `Authorization` header against the `CRON_SECRET` env var.
+    ///
+    /// Returns `false` when `CRON_SECRET` is not set.
+    fn verify_cron_secret(&self) -> bool;
+}
+
+impl RequestExt for Request {
+    fn invocation(&self) -> Option<&Invocation> {
+        self.extensions().get::<Invocation>()
+    }
+
+    fn deadline_token(&self) -> CancellationToken {
+        self.invocation()
+            .map(|i| i.deadline_token.clone())
+            .unwrap_or_default()
+    }
+
+    fn received_at(&self) -> Instant {
+        self.invocation()
+            .map(|i| i.received_at)
+            .unwrap_or_else(Instant::now)
+    }
+
+    fn trace_context(&self) -> Option<&TraceContext> {
+        self.extensions().get::<TraceContext>()
+    }
+
+    fn matched_route(&self) -> Option<&str> {
+        self.extensions().get::<MatchedRoute>().map(|r| r.0)
+    }
+
+    fn path_segments(&self) -> PathSegments<'_> {
+        PathSegments::new(self.uri().path())
+    }
+
+    fn has_protection_bypass(&self) -> bool {
+        protection::has_bypass_header(self.headers())
+    }
+
+    fn protection_bypass(&self, expected: &str) -> ProtectionBypass {
+        protection::check_bypass(self.headers(), expected)
+    }
+
+    fn forwarded(&self) -> Vec<ForwardedElement> {
+        forwarded::forwarded(self.headers())
+    }
+
+    fn client_ip(&self) -> Option<IpAddr> {
+        forwarded::client_ip(self.headers())
+    }
+
+    fn scheme(&self) -> String {
+        forwarded::public_scheme(self.headers())
+    }
+
+    fn is_secure(&self) -> bool {
+        forwarded::is_secure(self.headers(), self.uri())
+    }
+
+    fn expects_continue(&self) -> bool {
+        self.headers()
+            .get(header::EXPECT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("100-continue"))
+    }
+
+    fn accepts_encoding(&self, encoding: &str) -> bool {
+        headers::accepts_encoding(self.headers(), encoding)
+    }
+
+    fn is_prefetch(&self) -> bool {
+        headers::is_prefetch(self.headers())
+    }
+
+    fn host(&self) -> Option<String> {
+        forwarded::public_host(self.headers(), self.uri())
+    }
+
+    fn base_url(&self) -> Option<String> {
+        forwarded::base_url(self.headers(), self.uri())
+    }
+
+    fn absolute_url(&self, path: &str) -> Option<String> {
+        forwarded::absolute_url(self.headers(), self.uri(), path)
+    }
+
+    fn query<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        query::from_query(self.uri().query().unwrap_or_default())
+    }
+
+    fn cursor(&self) -> Option<String> {
+        pagination::cursor(self.uri().query().unwrap_or_default())
+    }
+
+    fn limit(&self) -> usize {
+        let query = self.uri().query().unwrap_or_default();
+        pagination::limit(query, pagination::DEFAULT_LIMIT, pagination::MAX_LIMIT)
+    }
+
+    fn expect_json<T: DeserializeOwned>(&self) -> Result<T, Response<Body>> {
+        json::expect_json(self)
+    }
+
+    fn body_any(&self) -> Result<ParsedBody, Error> {
+        parsed_body::parse_body(self)
+    }
+
+    fn json_merge_patch(&self) -> Result<serde_json::Value, Error> {
+        merge_patch::parse_merge_patch(self)
+    }
+
+    #[cfg(feature = "json5")]
+    fn json5<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(json5::from_str(std::str::from_utf8(self.body())?)?)
+    }
+
+    fn json_seq_stream<T: DeserializeOwned>(&self) -> JsonSeqRecords<'_, T> {
+        json_stream::parse_json_seq(self.body())
+    }
+
+    fn cache_status(&self) -> Option<CacheStatus> {
+        CacheStatus::from_headers(self.headers())
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        headers::content_length(self.headers())
+    }
+
+    fn vercel_id(&self) -> Option<VercelId> {
+        VercelId::from_headers(self.headers())
+    }
+
+    fn if_modified_since(&self) -> Option<SystemTime> {
+        conditional::if_modified_since(self.headers())
+    }
+
+    fn if_unmodified_since(&self) -> Option<SystemTime> {
+        conditional::if_unmodified_since(self.headers())
+    }
+
+    fn precondition_passes(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        conditional::precondition_passes(self.headers(), etag, last_modified)
+    }
+
+    fn cron_context(&self) -> Option<CronContext> {
+        cron::cron_context(self)
+    }
+
+    fn verify_cron_secret(&self) -> bool {
+        match std::env::var("CRON_SECRET") {
+            Ok(secret) if !secret.is_empty() => cron::verify_secret(self, &secret),
+            _ => false,
+        }
+    }
+}
+
+/// Extensions for [`Response`] bodies, for middleware that inspects responses.
+pub trait ResponseExt {
+    /// The body of a buffered response, `None` when it is streaming, see [`Body::peek`].
+    fn peek_body(&self) -> Option<&[u8]>;
+
+    /// Duplicates the body chunks to `sink` as they are sent, see [`Body::tee`].
+    fn tee_body(self, sink: impl FnMut(&[u8]) + Send + 'static) -> Self;
+
+    /// Replaces the status, e.g. to turn a `200` into a `202`.
+    ///
+    /// Like [`ResponseExt::with_header`], this also works for streaming responses: the head is
+    /// only sent once the handler returned the response.
+    fn map_status(self, status: StatusCode) -> Self;
+
+    /// Sets a header, appending for multi-value headers, see [`headers::merge_header`].
+    fn with_header(self, name: HeaderName, value: HeaderValue) -> Self;
+
+    /// Marks the resource as deprecated since `since`, e.g. for the routes of an old API
+    /// version.
+    ///
+    /// Sets `Deprecation` (RFC 9745), `Sunset` (RFC 8594) when the resource will go away at a
+    /// known time, and a `Link` with `rel=deprecation` to documentation for migrating.
+    fn deprecate(self, since: SystemTime, sunset: Option<SystemTime>, link: Option<&str>) -> Self;
+
+    /// Runs `task` after the response was delivered, like
+    /// [`RequestExt::wait_until_after_response`] but declared along with the response.
+    ///
+    /// Tasks attached to the same response run concurrently, see [`crate::lifecycle`].
+    fn and_then_background<F, Fut>(self, task: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static;
+}
+
+impl ResponseExt for Response<Body> {
+    fn peek_body(&self) -> Option<&[u8]> {
+        self.body().peek()
+    }
+
+    fn tee_body(self, sink: impl FnMut(&[u8]) + Send + 'static) -> Self {
+        self.map(|body| body.tee(sink))
+    }
+
+    fn map_status(mut self, status: StatusCode) -> Self {
+        *self.status_mut() = status;
+        self
+    }
+
+    fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        headers::merge_header(self.headers_mut(), name, value);
+        self
+    }
+
+    fn deprecate(
+        mut self,
+        since: SystemTime,
+        sunset: Option<SystemTime>,
+        link: Option<&str>,
+    ) -> Self {
+        let headers = self.headers_mut();
+        let since = since
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        headers.insert(
+            "deprecation",
+            format!("@{}", since).parse().expect("digits are valid"),
+        );
+        if let Some(sunset) = sunset {
+            let sunset = httpdate::fmt_http_date(sunset);
+            headers.insert("sunset", sunset.parse().expect("HTTP dates are valid"));
+        }
+        if let Some(link) = link.and_then(|link| headers::Link::deprecation(link).to_header_value())
+        {
+            headers.append(header::LINK, link);
+        }
+        self
+    }
+
+    fn and_then_background<F, Fut>(mut self, task: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.extensions_mut()
+            .get_or_insert_default::<lifecycle::BackgroundTasks>()
+            .push(task);
+        self
+    }
+}
+
+/// Extensions for [`lambda_http::http::response::Builder`].
+pub trait ResponseBuilderExt {
+    /// Applies a batch of headers.
+    ///
+    /// Multi-value headers such as `Set-Cookie` are appended, any other header replaces the
+    /// value that was set before.
+    fn headers<I>(self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (HeaderName, HeaderValue)>;
+
+    /// Same as [`ResponseBuilderExt::headers`] for a whole [`HeaderMap`].
+    fn merge_headers(self, headers: HeaderMap) -> Self;
+
+    /// Declares that the response depends on the request header `name`, see
+    /// [`headers::VarySet`].
+    fn vary(self, name: &str) -> Self;
+
+    /// Makes browsers download the body as `filename`, see [`headers::content_disposition`].
+    fn attachment(self, filename: &str) -> Self;
+
+    /// Lets browsers display the body, using `filename` when it is saved.
+    fn inline(self, filename: &str) -> Self;
+
+    /// Hints resources the page will need as `Link` headers.
+    ///
+    /// A function cannot send `103 Early Hints`: the Lambda Runtime API accepts exactly one
+    /// response head per invocation. The links are added to the final response instead,
+    /// where browsers still start the fetches as soon as the headers arrive.
+    fn early_hints(self, links: &[headers::Link]) -> Self;
+
+    /// Sets `Last-Modified`, which clients send back in `If-Modified-Since`.
+    fn last_modified(self, time: SystemTime) -> Self;
+}
+
+impl ResponseBuilderExt for Builder {
+    fn headers<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = (HeaderName, HeaderValue)>,
+    {
+        // A builder that already failed has no header map, keep its error around
+        if let Some(map) = self.headers_mut() {
+            for (name, value) in iter {
+                headers::merge_header(map, name, value);
+            }
+        }
+        self
+    }
+
+    fn merge_headers(mut self, other: HeaderMap) -> Self {
+        if let Some(map) = self.headers_mut() {
+            headers::merge_headers(map, other);
+        }
+        self
+    }
+
+    fn vary(mut self, name: &str) -> Self {
+        if let Some(extensions) = self.extensions_mut() {
+            headers::add_vary(extensions, name);
+        }
+        self
+    }
+
+    fn attachment(self, filename: &str) -> Self {
+        self.header(
+            header::CONTENT_DISPOSITION,
+            headers::content_disposition("attachment", filename),
+        )
+    }
+
+    fn inline(self, filename: &str) -> Self {
+        self.header(
+            header::CONTENT_DISPOSITION,
+            headers::content_disposition("inline", filename),
+        )
+    }
+
+    fn early_hints(self, links: &[headers::Link]) -> Self {
+        links
+            .iter()
+            .filter_map(headers::Link::to_header_value)
+            .fold(self, |builder, link| builder.header(header::LINK, link))
+    }
+
+    fn last_modified(self, time: SystemTime) -> Self {
+        self.header(header::LAST_MODIFIED, httpdate::fmt_http_date(time))
+    }
+}
+
+#[cfg(all(test, feature = "json5"))]
+mod tests {
+    use super::RequestExt;
+    use lambda_http::{Request, RequestPayloadExt};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn it_parses_lenient_json_only_on_request() {
+        let body = r#"{
+            // emitted by a tool that adds comments
+            name: "demo",
+            tags: ["a", "b",],
+        }"#;
+        let mut request = Request::new(body.into());
+        request
+            .headers_mut()
+            .insert("content-type", "application/json".parse().unwrap());
+
+        let config: Config = request.json5().unwrap();
+        assert_eq!(config.name, "demo");
+        assert_eq!(config.tags, ["a", "b"]);
+        // The strict parser still refuses the same body
+        assert!(request.payload::<Config>().is_err());
+        assert!(Request::new("{".into()).json5::<Config>().is_err());
+    }
+}
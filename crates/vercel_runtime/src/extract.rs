@@ -0,0 +1,252 @@
+//! Typed handler arguments, as an alternative to pulling values out of the [`Request`] by hand.
+//!
+//! Every argument of a function wrapped with [`handler`] implements [`FromRequest`]. When one
+//! fails to extract, its rejection is sent back and the function is not called:
+//!
+//! ```no_run
+//! # use serde::Deserialize;
+//! # use vercel_runtime::{extract::{self, Bearer, Json, Path}, run, Error, StatusCode};
+//! #[derive(Deserialize)]
+//! struct Step {
+//!     step: u32,
+//! }
+//!
+//! # async fn example() -> Result<(), Error> {
+//! run(extract::handler(
+//!     |Path(step): Path<Step>, Bearer(_token): Bearer, Json(input): Json<serde_json::Value>| async move {
+//!         Ok((StatusCode::OK, serde_json::json!({ "step": step.step, "input": input })))
+//!     },
+//! ))
+//! .await
+//! # }
+//! ```
+//!
+//! Handlers taking the plain [`Request`] keep working as before. Implement [`FromRequest`] for
+//! custom extractors, e.g. a session loaded from a cookie.
+use crate::{json, query, routing::MatchedRoute, Body, Error, IntoResponse, Request};
+use futures_util::future::{ready, Either, MapOk, Ready, TryFutureExt};
+use lambda_http::http::{header, StatusCode};
+use lambda_http::Response;
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::future::Future;
+use vercel_runtime_router::Route;
+
+/// A value that can be taken from a request, or the response to reject the request with.
+pub trait FromRequest: Sized {
+    #[allow(clippy::result_large_err)]
+    fn from_request(req: &Request) -> Result<Self, Response<Body>>;
+}
+
+/// A JSON body, rejected like [`crate::RequestExt::expect_json`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(req: &Request) -> Result<Self, Response<Body>> {
+        json::expect_json(req).map(Json)
+    }
+}
+
+/// The query string, see [`crate::query`]. Rejected with `400`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(req: &Request) -> Result<Self, Response<Body>> {
+        query::from_query(req.uri().query().unwrap_or_default())
+            .map(Query)
+            .map_err(|e| rejection(StatusCode::BAD_REQUEST, "invalid query string", e))
+    }
+}
+
+/// The dynamic segments of the route [`crate::bundled_api`] dispatched to, by name.
+///
+/// For `api/step-v4/[step].rs`, `Path<Step>` fills `Step { step: u32 }`. Rejected with `400`
+/// when a segment does not parse, and with `500` for requests that did not go through the
+/// bundled router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Path<T> {
+    fn from_request(req: &Request) -> Result<Self, Response<Body>> {
+        let Some(MatchedRoute(template)) = req.extensions().get::<MatchedRoute>() else {
+            let message = "the request was not dispatched by bundled_api";
+            return Err(rejection(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no route parameters",
+                message,
+            ));
+        };
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        for (name, value) in Route::from(*template).params(req.uri().path()) {
+            params.append_pair(&name, &percent_decode_str(&value).decode_utf8_lossy());
+        }
+        query::from_query(&params.finish())
+            .map(Path)
+            .map_err(|e| rejection(StatusCode::BAD_REQUEST, "invalid path parameters", e))
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header. Rejected with `401`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bearer(pub String);
+
+impl FromRequest for Bearer {
+    fn from_request(req: &Request) -> Result<Self, Response<Body>> {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .filter(|token| !token.is_empty());
+        match token {
+            Some(token) => Ok(Bearer(token.to_owned())),
+            None => {
+                let mut response = rejection(
+                    StatusCode::UNAUTHORIZED,
+                    "missing bearer token",
+                    "expected an `Authorization: Bearer <token>` header",
+                );
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    header::HeaderValue::from_static("Bearer"),
+                );
+                Err(response)
+            }
+        }
+    }
+}
+
+fn rejection(status: StatusCode, error: &str, message: impl std::fmt::Display) -> Response<Body> {
+    (
+        status,
+        json!({ "error": error, "message": message.to_string() }),
+    )
+        .into_response()
+}
+
+/// A function of [`FromRequest`] arguments, see [`handler`].
+pub trait Handler<Args> {
+    type Future: Future<Output = Result<Response<Body>, Error>>;
+
+    fn call(&mut self, req: Request) -> Self::Future;
+}
+
+type Extracted<Fut, R> =
+    Either<Ready<Result<Response<Body>, Error>>, MapOk<Fut, fn(R) -> Response<Body>>>;
+
+macro_rules! impl_handler {
+    ($($arg:ident),+) => {
+        impl<F, Fut, R, $($arg),+> Handler<($($arg,)+)> for F
+        where
+            F: FnMut($($arg),+) -> Fut,
+            Fut: Future<Output = Result<R, Error>>,
+            R: IntoResponse,
+            $($arg: FromRequest,)+
+        {
+            type Future = Extracted<Fut, R>;
+
+            #[allow(non_snake_case)]
+            fn call(&mut self, req: Request) -> Self::Future {
+                $(
+                    let $arg = match $arg::from_request(&req) {
+                        Ok(value) => value,
+                        Err(rejection) => return Either::Left(ready(Ok(rejection))),
+                    };
+                )+
+                Either::Right(self($($arg),+).map_ok(R::into_response as fn(R) -> Response<Body>))
+            }
+        }
+    };
+}
+
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+
+/// Turns a function of up to four extractors into a handler for [`crate::run`].
+///
+/// Arguments are extracted in order, the first rejection is the response.
+pub fn handler<H, Args>(mut handler: H) -> impl FnMut(Request) -> H::Future
+where
+    H: Handler<Args>,
+{
+    move |req| handler.call(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handler, Bearer, FromRequest, Json, Path, Query};
+    use crate::routing::MatchedRoute;
+    use crate::Request;
+    use lambda_http::http::{self, StatusCode};
+    use lambda_http::Body;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Step {
+        step: u32,
+    }
+
+    fn request(uri: &str, body: &str) -> Request {
+        let mut req = http::Request::builder()
+            .uri(uri)
+            .header("authorization", "Bearer t0ken")
+            .body(Body::from(body))
+            .unwrap();
+        req.extensions_mut()
+            .insert(MatchedRoute("/api/step-v4/[step]"));
+        req
+    }
+
+    #[test]
+    fn it_extracts_typed_values() {
+        let req = request("/api/step-v4/2?step=5", r#"{"n":1}"#);
+        assert_eq!(
+            Path::<Step>::from_request(&req).unwrap().0,
+            Step { step: 2 }
+        );
+        assert_eq!(
+            Query::<Step>::from_request(&req).unwrap().0,
+            Step { step: 5 }
+        );
+        assert_eq!(
+            Json::<Value>::from_request(&req).unwrap().0,
+            json!({"n": 1})
+        );
+        assert_eq!(Bearer::from_request(&req).unwrap().0, "t0ken");
+
+        let bad = request("/api/step-v4/two", "");
+        let rejected = Path::<Step>::from_request(&bad).unwrap_err();
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        let anonymous = http::Request::builder().body(Body::Empty).unwrap();
+        let rejected = Bearer::from_request(&anonymous).unwrap_err();
+        assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(rejected.headers()["www-authenticate"], "Bearer");
+    }
+
+    #[tokio::test]
+    async fn it_rejects_before_calling_the_handler() {
+        let mut step = handler(
+            |Path(step): Path<Step>, Json(input): Json<Value>| async move {
+                Ok((StatusCode::OK, json!({ "step": step.step, "input": input })))
+            },
+        );
+
+        let response = step(request("/api/step-v4/3", r#""hi""#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let crate::Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        assert_eq!(body, r#"{"input":"hi","step":3}"#);
+
+        let response = step(request("/api/step-v4/3", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,373 @@
+use lambda_http::http::{HeaderMap, Uri};
+use std::net::IpAddr;
+
+/// One hop of a `Forwarded` header (RFC 7239).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedElement {
+    /// The node that made the request to the proxy, usually the client address.
+    pub for_: Option<String>,
+    /// The interface where the request came in to the proxy.
+    pub by: Option<String>,
+    pub host: Option<String>,
+    pub proto: Option<String>,
+}
+
+impl ForwardedElement {
+    /// The `for=` node as an IP address, if it is one (ports and IPv6 brackets are stripped).
+    pub fn for_ip(&self) -> Option<IpAddr> {
+        self.for_.as_deref().and_then(parse_node_ip)
+    }
+}
+
+/// Proxy hops from `Forwarded`, or synthesized from `X-Forwarded-*` when it is absent.
+///
+/// Vercel sets `X-Forwarded-*` itself but passes `Forwarded` through from the client unchanged,
+/// so on Vercel these hops are whatever the client sent. [`client_ip`], [`public_scheme`] and
+/// [`public_host`] only read `X-Forwarded-*` there.
+pub fn forwarded(headers: &HeaderMap) -> Vec<ForwardedElement> {
+    let values = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+
+    if !values.is_empty() {
+        return values.into_iter().flat_map(parse_forwarded).collect();
+    }
+    x_forwarded(headers)
+}
+
+/// Proxy hops synthesized from `X-Forwarded-For`, `-Proto` and `-Host`.
+fn x_forwarded(headers: &HeaderMap) -> Vec<ForwardedElement> {
+    let header = |name: &str| -> Option<String> {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(',').next().unwrap_or_default().trim().to_owned())
+            .filter(|v| !v.is_empty())
+    };
+
+    let mut elements = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| ForwardedElement {
+            for_: Some(v.to_owned()),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    let proto = header("x-forwarded-proto");
+    let host = header("x-forwarded-host");
+    if proto.is_some() || host.is_some() {
+        if elements.is_empty() {
+            elements.push(ForwardedElement::default());
+        }
+        // The original protocol and host describe the first hop
+        elements[0].proto = proto;
+        elements[0].host = host;
+    }
+
+    elements
+}
+
+/// Whether the function runs on Vercel, where the edge network sets `VERCEL=1`.
+fn on_vercel() -> bool {
+    std::env::var("VERCEL").is_ok_and(|v| v == "1")
+}
+
+/// The hops to trust: only the ones Vercel set itself when running there.
+fn trusted_hops(headers: &HeaderMap, on_vercel: bool) -> Vec<ForwardedElement> {
+    if on_vercel {
+        x_forwarded(headers)
+    } else {
+        forwarded(headers)
+    }
+}
+
+/// Address of the client.
+///
+/// On Vercel this is `X-Real-IP`, then `X-Forwarded-For`, which the edge network sets; a
+/// `Forwarded` header is ignored there because the client controls it. Elsewhere `Forwarded`
+/// is preferred over `X-Forwarded-For` and `X-Real-IP`, so only trust it behind a proxy that
+/// strips or overwrites these headers.
+pub fn client_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    client_ip_from(headers, on_vercel())
+}
+
+fn client_ip_from(headers: &HeaderMap, on_vercel: bool) -> Option<IpAddr> {
+    let real_ip = || {
+        headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_node_ip)
+    };
+    let first_hop = || {
+        trusted_hops(headers, on_vercel)
+            .first()
+            .and_then(ForwardedElement::for_ip)
+    };
+    if on_vercel {
+        real_ip().or_else(first_hop)
+    } else {
+        first_hop().or_else(real_ip)
+    }
+}
+
+/// Scheme the client used to reach the deployment, `https` unless a proxy says otherwise.
+///
+/// Ignores `Forwarded` on Vercel, see [`forwarded`].
+pub fn public_scheme(headers: &HeaderMap) -> String {
+    public_scheme_from(headers, on_vercel())
+}
+
+fn public_scheme_from(headers: &HeaderMap, on_vercel: bool) -> String {
+    trusted_hops(headers, on_vercel)
+        .into_iter()
+        .next()
+        .and_then(|e| e.proto)
+        .map(|p| p.to_ascii_lowercase())
+        .unwrap_or_else(|| "https".to_owned())
+}
+
+/// Whether the request arrived over HTTPS, from `X-Forwarded-Proto` only.
+///
+/// Unlike [`public_scheme`] this ignores `Forwarded`, which Vercel passes through from the
+/// client unchanged, and it does not default to `https`: without `X-Forwarded-Proto` only an
+/// `https` request URI counts as secure.
+pub fn is_secure(headers: &HeaderMap, uri: &Uri) -> bool {
+    match headers.get("x-forwarded-proto") {
+        Some(value) => value.to_str().is_ok_and(|value| {
+            let proto = value.split(',').next().unwrap_or_default().trim();
+            proto.eq_ignore_ascii_case("https")
+        }),
+        None => uri.scheme_str() == Some("https"),
+    }
+}
+
+/// Host the client used to reach the deployment.
+///
+/// Checks `Forwarded`/`X-Forwarded-Host`, then `Host` and the request URI, and finally the
+/// `VERCEL_URL` deployment URL. Ignores `Forwarded` on Vercel, see [`forwarded`].
+pub fn public_host(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    public_host_from(headers, uri, on_vercel())
+}
+
+fn public_host_from(headers: &HeaderMap, uri: &Uri, on_vercel: bool) -> Option<String> {
+    trusted_hops(headers, on_vercel)
+        .into_iter()
+        .next()
+        .and_then(|e| e.host)
+        .or_else(|| {
+            headers
+                .get("host")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        })
+        .or_else(|| uri.authority().map(|a| a.to_string()))
+        .or_else(|| std::env::var("VERCEL_URL").ok())
+        .filter(|h| !h.is_empty())
+}
+
+/// `scheme://host` of the deployment as the client reached it, without a trailing slash.
+pub fn base_url(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    let host = public_host(headers, uri)?;
+    Some(format!("{}://{}", public_scheme(headers), host))
+}
+
+/// Joins the public scheme and host with `path`.
+pub fn absolute_url(headers: &HeaderMap, uri: &Uri, path: &str) -> Option<String> {
+    let base = base_url(headers, uri)?;
+    Some(format!("{}/{}", base, path.trim_start_matches('/')))
+}
+
+/// Parses a single `Forwarded` header value into its comma separated elements.
+pub fn parse_forwarded(value: &str) -> Vec<ForwardedElement> {
+    split_quoted(value, ',')
+        .into_iter()
+        .filter(|e| !e.trim().is_empty())
+        .map(|element| {
+            let mut parsed = ForwardedElement::default();
+            for pair in split_quoted(element, ';') {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = Some(unquote(value.trim()));
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "for" => parsed.for_ = value,
+                    "by" => parsed.by = value,
+                    "host" => parsed.host = value,
+                    "proto" => parsed.proto = value,
+                    _ => {}
+                }
+            }
+            parsed
+        })
+        .collect()
+}
+
+fn split_quoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => out.extend(chars.next()),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+        None => value.to_owned(),
+    }
+}
+
+fn parse_node_ip(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    // `[2001:db8::1]:4711` or `[2001:db8::1]`
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    // `192.0.2.60:8080`
+    node.rsplit_once(':')?.0.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        absolute_url, base_url, client_ip_from, forwarded, parse_forwarded, public_host_from,
+        public_scheme, public_scheme_from,
+    };
+    use lambda_http::http::{HeaderMap, Uri};
+    use std::net::IpAddr;
+
+    #[test]
+    fn it_parses_forwarded_elements() {
+        let elements = parse_forwarded(
+            r#"for=192.0.2.60;proto=http;by=203.0.113.43, For="[2001:db8:cafe::17]:4711";host="a\"b""#,
+        );
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].for_.as_deref(), Some("192.0.2.60"));
+        assert_eq!(elements[0].proto.as_deref(), Some("http"));
+        assert_eq!(elements[0].by.as_deref(), Some("203.0.113.43"));
+        assert_eq!(elements[1].host.as_deref(), Some("a\"b"));
+        assert_eq!(
+            elements[1].for_ip(),
+            Some("2001:db8:cafe::17".parse::<IpAddr>().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_prefers_forwarded_over_x_forwarded_for() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 10.0.0.2".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        assert_eq!(
+            client_ip_from(&headers, false),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(forwarded(&headers)[0].proto.as_deref(), Some("https"));
+        assert_eq!(forwarded(&headers).len(), 2);
+
+        headers.insert("forwarded", "for=192.0.2.60:8080".parse().unwrap());
+        assert_eq!(
+            client_ip_from(&headers, false),
+            Some("192.0.2.60".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_x_real_ip() {
+        let mut headers = HeaderMap::new();
+        headers.insert("forwarded", "for=unknown".parse().unwrap());
+        headers.insert("x-real-ip", "::1".parse().unwrap());
+        assert_eq!(
+            client_ip_from(&headers, false),
+            Some("::1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_ignores_forwarded_on_vercel() {
+        let mut headers = HeaderMap::new();
+        let uri = Uri::from_static("/");
+        headers.insert(
+            "forwarded",
+            "for=1.2.3.4;proto=http;host=evil.com".parse().unwrap(),
+        );
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        headers.insert("x-forwarded-host", "example.com".parse().unwrap());
+        assert_eq!(
+            client_ip_from(&headers, true),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(public_scheme_from(&headers, true), "https");
+        assert_eq!(
+            public_host_from(&headers, &uri, true).as_deref(),
+            Some("example.com")
+        );
+
+        headers.insert("x-real-ip", "198.51.100.2".parse().unwrap());
+        assert_eq!(
+            client_ip_from(&headers, true),
+            Some("198.51.100.2".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip_from(&headers, false),
+            Some("1.2.3.4".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_builds_absolute_urls() {
+        let mut headers = HeaderMap::new();
+        let uri = Uri::from_static("/api/step/1");
+        headers.insert("host", "internal.vercel.app".parse().unwrap());
+        assert_eq!(public_scheme(&headers), "https");
+        assert_eq!(
+            absolute_url(&headers, &uri, "/api/step/2").as_deref(),
+            Some("https://internal.vercel.app/api/step/2")
+        );
+
+        headers.insert("x-forwarded-host", "example.com".parse().unwrap());
+        headers.insert("x-forwarded-proto", "http".parse().unwrap());
+        assert_eq!(
+            absolute_url(&headers, &uri, "next").as_deref(),
+            Some("http://example.com/next")
+        );
+        assert_eq!(
+            base_url(&headers, &uri).as_deref(),
+            Some("http://example.com")
+        );
+    }
+}
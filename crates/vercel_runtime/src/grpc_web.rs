@@ -0,0 +1,340 @@
+//! [gRPC-Web](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md) framing.
+//!
+//! Every message travels as a frame of a flag byte, a big endian `u32` length and the
+//! encoded message. The status goes into a final trailer frame instead of HTTP trailers, and
+//! `application/grpc-web-text` wraps all of it in base64. Encoding the messages themselves,
+//! e.g. with `prost`, is left to the handler.
+//!
+//! ```no_run
+//! # use vercel_runtime::{grpc_web::{self, Status}, Body, Error, Request, Response};
+//! # fn handle(_: &[u8]) -> Result<Vec<u8>, Status> { Ok(Vec::new()) }
+//! async fn handler(req: Request) -> Result<Response<Body>, Error> {
+//!     let encoding = grpc_web::Encoding::of(&req).unwrap_or(grpc_web::Encoding::Binary);
+//!     let reply = grpc_web::read_message(&req).and_then(|message| handle(&message));
+//!     match reply {
+//!         Ok(reply) => grpc_web::response(encoding, &[reply], &Status::ok()),
+//!         Err(status) => grpc_web::response(encoding, &[] as &[&[u8]], &status),
+//!     }
+//! }
+//! ```
+use crate::streaming::{self, BodySender, HeadersMut};
+use crate::Body;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::{BufMut, Bytes, BytesMut};
+use lambda_http::http::{header, response::Builder, StatusCode};
+use lambda_http::{Error, Request, Response};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::fmt;
+
+/// Flag of a frame carrying a message.
+const DATA_FLAG: u8 = 0x00;
+/// Flag of the frame carrying the trailers.
+const TRAILER_FLAG: u8 = 0x80;
+/// Bit set on the flag of a compressed message.
+const COMPRESSED_FLAG: u8 = 0x01;
+const FRAME_HEADER_LEN: usize = 5;
+
+/// `grpc-message` is percent-encoded outside of printable ASCII.
+const MESSAGE_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%');
+
+/// How the frames are put on the wire, taken from the request `Content-Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `application/grpc-web`, frames as they are.
+    Binary,
+    /// `application/grpc-web-text`, frames in base64.
+    Text,
+}
+
+impl Encoding {
+    /// Reads `application/grpc-web` and `application/grpc-web-text`, with or without a
+    /// `+proto` style suffix.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let base = mime.split('+').next().unwrap_or_default();
+        match base {
+            "application/grpc-web" => Some(Encoding::Binary),
+            "application/grpc-web-text" => Some(Encoding::Text),
+            _ => None,
+        }
+    }
+
+    pub fn of(request: &Request) -> Option<Self> {
+        request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::from_content_type)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Binary => "application/grpc-web+proto",
+            Encoding::Text => "application/grpc-web-text+proto",
+        }
+    }
+
+    fn encode(self, frames: Bytes) -> Bytes {
+        match self {
+            Encoding::Binary => frames,
+            Encoding::Text => BASE64_STANDARD.encode(&frames).into(),
+        }
+    }
+}
+
+/// A gRPC status, sent in the trailer frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub code: u32,
+    pub message: String,
+}
+
+impl Status {
+    pub const OK: u32 = 0;
+    pub const INVALID_ARGUMENT: u32 = 3;
+    pub const UNIMPLEMENTED: u32 = 12;
+    pub const INTERNAL: u32 = 13;
+
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        Status {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::new(Self::OK, "")
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_ARGUMENT, message)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "grpc-status {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Status {}
+
+/// The single message of a unary call.
+///
+/// Fails with `INVALID_ARGUMENT` unless the body is exactly one complete data frame, and with
+/// `UNIMPLEMENTED` for compressed messages.
+pub fn read_message(request: &Request) -> Result<Bytes, Status> {
+    let encoding = Encoding::of(request)
+        .ok_or_else(|| Status::invalid_argument("expected a gRPC-Web content type"))?;
+    let mut messages = decode_messages(encoding, request.body())?;
+    match (messages.pop(), messages.is_empty()) {
+        (Some(message), true) => Ok(message),
+        _ => Err(Status::invalid_argument("expected exactly one message")),
+    }
+}
+
+/// Every message of a request body, in order.
+pub fn decode_messages(encoding: Encoding, body: &[u8]) -> Result<Vec<Bytes>, Status> {
+    let body = match encoding {
+        Encoding::Binary => Bytes::copy_from_slice(body),
+        Encoding::Text => decode_text(body)?,
+    };
+
+    let mut messages = Vec::new();
+    let mut rest = &body[..];
+    while !rest.is_empty() {
+        if rest.len() < FRAME_HEADER_LEN {
+            return Err(Status::invalid_argument("truncated frame header"));
+        }
+        let flag = rest[0];
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let payload = rest
+            .get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)
+            .ok_or_else(|| Status::invalid_argument("truncated frame"))?;
+        if flag & COMPRESSED_FLAG != 0 {
+            return Err(Status::new(
+                Status::UNIMPLEMENTED,
+                "compressed messages are not supported",
+            ));
+        }
+        // Clients do not send trailers, anything but data is skipped
+        if flag & TRAILER_FLAG == 0 {
+            messages.push(body.slice_ref(payload));
+        }
+        rest = &rest[FRAME_HEADER_LEN + len..];
+    }
+    Ok(messages)
+}
+
+/// Decodes a text body, which may be several padded base64 chunks one after the other.
+fn decode_text(body: &[u8]) -> Result<Bytes, Status> {
+    let text: Vec<u8> = body
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    if !text.len().is_multiple_of(4) {
+        return Err(Status::invalid_argument("invalid base64 body"));
+    }
+
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let mut start = 0;
+    for (i, quad) in text.chunks(4).enumerate() {
+        let end = (i + 1) * 4;
+        if quad.ends_with(b"=") || end == text.len() {
+            BASE64_STANDARD
+                .decode_vec(&text[start..end], &mut decoded)
+                .map_err(|_| Status::invalid_argument("invalid base64 body"))?;
+            start = end;
+        }
+    }
+    Ok(decoded.into())
+}
+
+/// A data frame carrying `message`.
+pub fn data_frame(message: &[u8]) -> Bytes {
+    frame(DATA_FLAG, message)
+}
+
+/// The trailer frame carrying `grpc-status` and `grpc-message`.
+pub fn trailer_frame(status: &Status) -> Bytes {
+    let mut trailers = format!("grpc-status:{}\r\n", status.code);
+    if !status.message.is_empty() {
+        let message = utf8_percent_encode(&status.message, MESSAGE_ENCODE_SET);
+        trailers.push_str(&format!("grpc-message:{}\r\n", message));
+    }
+    frame(TRAILER_FLAG, trailers.as_bytes())
+}
+
+fn frame(flag: u8, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.put_u8(flag);
+    frame.put_u32(payload.len() as u32);
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+/// A buffered response with `messages` followed by the trailer frame.
+///
+/// The HTTP status is `200` whatever the gRPC status, as gRPC-Web clients expect.
+pub fn response<M: AsRef<[u8]>>(
+    encoding: Encoding,
+    messages: &[M],
+    status: &Status,
+) -> Result<Response<Body>, Error> {
+    let mut frames = BytesMut::new();
+    for message in messages {
+        frames.put(data_frame(message.as_ref()));
+    }
+    frames.put(trailer_frame(status));
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, encoding.content_type())
+        .body(Body::Binary(encoding.encode(frames.freeze()).into()))?)
+}
+
+/// Writer for a server streaming call, every message is sent as soon as it is written.
+#[derive(Debug)]
+pub struct GrpcWebResponse {
+    sender: BodySender,
+    encoding: Encoding,
+}
+
+impl GrpcWebResponse {
+    /// Creates the writer and the response to return from the handler.
+    pub fn new(builder: Builder, encoding: Encoding) -> Result<(Self, Response<Body>), Error> {
+        let (mut sender, body) = streaming::channel();
+        let response = builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, encoding.content_type())
+            .extension(sender.attach_headers())
+            .body(body)?;
+        Ok((GrpcWebResponse { sender, encoding }, response))
+    }
+
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        let frame = self.encoding.encode(data_frame(message));
+        self.sender.send(frame).await
+    }
+
+    /// Headers to add to the response until the first message is sent, see
+    /// [`crate::sse::SseResponse::headers_mut`].
+    pub fn headers_mut(&self) -> Result<HeadersMut<'_>, Error> {
+        self.sender.headers_mut()
+    }
+
+    /// Sends the trailer frame, which ends the call. Clients treat a stream without one as
+    /// failed.
+    pub async fn finish(mut self, status: &Status) -> Result<(), Error> {
+        let frame = self.encoding.encode(trailer_frame(status));
+        self.sender.send(frame).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{data_frame, decode_messages, read_message, response, Encoding, Status};
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use lambda_http::{http, Body, Request};
+
+    fn request(content_type: &str, body: Vec<u8>) -> Request {
+        http::Request::builder()
+            .header("content-type", content_type)
+            .body(Body::Binary(body))
+            .unwrap()
+    }
+
+    #[test]
+    fn it_reads_binary_and_text_frames() {
+        let frame = data_frame(b"\x08\x96\x01");
+        assert_eq!(&frame[..], b"\x00\x00\x00\x00\x03\x08\x96\x01");
+
+        let binary = request("application/grpc-web+proto", frame.to_vec());
+        assert_eq!(read_message(&binary).unwrap(), &b"\x08\x96\x01"[..]);
+
+        // Text bodies may be several padded chunks
+        let chunks = [data_frame(b"a"), data_frame(b"bc")]
+            .iter()
+            .map(|frame| BASE64_STANDARD.encode(frame))
+            .collect::<String>();
+        let messages = decode_messages(Encoding::Text, chunks.as_bytes()).unwrap();
+        assert_eq!(messages, [&b"a"[..], &b"bc"[..]]);
+
+        let truncated = request("application/grpc-web", frame[..6].to_vec());
+        assert_eq!(
+            read_message(&truncated).unwrap_err().code,
+            Status::INVALID_ARGUMENT
+        );
+        let compressed = request("application/grpc-web", b"\x01\x00\x00\x00\x00".to_vec());
+        assert_eq!(
+            read_message(&compressed).unwrap_err().code,
+            Status::UNIMPLEMENTED
+        );
+    }
+
+    #[test]
+    fn it_writes_the_trailer_frame() {
+        let status = Status::new(Status::INTERNAL, "node 2 failed: 100%");
+        let response = response(Encoding::Text, &[b"ok"], &status).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/grpc-web-text+proto"
+        );
+
+        let crate::Body::Binary(body) = response.body() else {
+            panic!("expected a binary body");
+        };
+        let frames = BASE64_STANDARD.decode(body).unwrap();
+        let trailers = b"grpc-status:13\r\ngrpc-message:node 2 failed: 100%25\r\n";
+        assert_eq!(&frames[..7], b"\x00\x00\x00\x00\x02ok");
+        assert_eq!(frames[7], 0x80);
+        assert_eq!(&frames[8..12], (trailers.len() as u32).to_be_bytes());
+        assert_eq!(&frames[12..], trailers);
+    }
+}
@@ -0,0 +1,616 @@
+use lambda_http::http::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Extensions,
+};
+use std::{collections::BTreeSet, fmt};
+
+/// Headers that may legitimately be sent several times and are appended instead of replaced.
+pub const MULTI_VALUE_HEADERS: [HeaderName; 3] =
+    [header::SET_COOKIE, header::LINK, header::WARNING];
+
+pub fn is_multi_value(name: &HeaderName) -> bool {
+    MULTI_VALUE_HEADERS.contains(name)
+}
+
+/// Adds `value` to `headers`, appending for multi-value headers and replacing otherwise.
+pub fn merge_header(headers: &mut HeaderMap, name: HeaderName, value: HeaderValue) {
+    if is_multi_value(&name) {
+        headers.append(name, value);
+    } else {
+        headers.insert(name, value);
+    }
+}
+
+/// Merges every entry of `other` into `headers`, see [`merge_header`].
+pub fn merge_headers(headers: &mut HeaderMap, other: HeaderMap) {
+    let mut current = None;
+    for (name, value) in other {
+        // `HeaderMap::into_iter` only yields the name for the first value of each header
+        if let Some(name) = name {
+            current = Some(name);
+        }
+        if let Some(name) = &current {
+            merge_header(headers, name.clone(), value);
+        }
+    }
+}
+
+/// Headers whose values never appear in logs, see [`RedactedHeaders`].
+pub const REDACTED_HEADERS: [HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::COOKIE,
+    header::SET_COOKIE,
+    HeaderName::from_static("x-vercel-protection-bypass"),
+];
+
+/// Formats headers for logging, with the values of [`REDACTED_HEADERS`] and `extra` replaced
+/// by `<redacted:N bytes>`.
+pub struct RedactedHeaders<'a> {
+    pub headers: &'a HeaderMap,
+    pub extra: &'a [HeaderName],
+}
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.headers {
+            if REDACTED_HEADERS.contains(name) || self.extra.contains(name) {
+                map.entry(name, &format_args!("<redacted:{} bytes>", value.len()));
+            } else {
+                map.entry(name, &String::from_utf8_lossy(value.as_bytes()));
+            }
+        }
+        map.finish()
+    }
+}
+
+/// Headers that describe a single connection and must not be forwarded by proxies (RFC 9110).
+pub const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Strips [`HOP_BY_HOP_HEADERS`] and any header listed in `Connection`.
+pub fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect();
+    for name in listed.iter().chain(&HOP_BY_HOP_HEADERS) {
+        headers.remove(name);
+    }
+}
+
+/// Checks the size of every header (name and value) and of all headers combined.
+pub fn check_header_size(
+    headers: &HeaderMap,
+    max_per_header: usize,
+    max_total: usize,
+) -> Result<(), &'static str> {
+    let mut total = 0;
+    for (name, value) in headers {
+        let size = name.as_str().len() + value.len();
+        if size > max_per_header {
+            return Err("request header too large");
+        }
+        total += size;
+        if total > max_total {
+            return Err("request headers too large");
+        }
+    }
+    Ok(())
+}
+
+/// Whether `Accept-Encoding` allows the content coding `encoding` (e.g. `br`, `gzip`).
+///
+/// Codings with `q=0` are refused and `*` stands for every coding not listed. `identity` is
+/// acceptable unless excluded explicitly. Without the header only `identity` is accepted,
+/// since clients that omit it rarely decode anything.
+pub fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    let identity = encoding.eq_ignore_ascii_case("identity");
+    let mut wildcard = None;
+    for value in headers.get_all(header::ACCEPT_ENCODING) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for item in value.split(',') {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or_default().trim();
+            if coding.is_empty() {
+                continue;
+            }
+            let quality = params
+                .filter_map(|param| param.split_once('='))
+                .filter(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .find_map(|(_, q)| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if coding.eq_ignore_ascii_case(encoding) {
+                return quality > 0.0;
+            }
+            if coding == "*" {
+                wildcard = Some(quality > 0.0);
+            }
+        }
+    }
+    wildcard.unwrap_or(identity)
+}
+
+/// A `Content-Disposition` value for `disposition` (`attachment` or `inline`) and `filename`.
+///
+/// Control characters are dropped. Names that are not plain ASCII additionally get an RFC 8187
+/// `filename*` parameter, with an ASCII approximation in `filename` for older clients.
+pub fn content_disposition(disposition: &str, filename: &str) -> HeaderValue {
+    let filename: String = filename.chars().filter(|c| !c.is_control()).collect();
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() => c,
+            _ => '_',
+        })
+        .collect();
+
+    let mut value = format!("{}; filename=\"{}\"", disposition, fallback);
+    if fallback != filename {
+        value.push_str("; filename*=UTF-8''");
+        for byte in filename.bytes() {
+            match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => value.push(byte as char),
+                b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|'
+                | b'~' => value.push(byte as char),
+                _ => value.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+    }
+    // Only visible ASCII is left at this point
+    HeaderValue::try_from(value).expect("content disposition is valid ASCII")
+}
+
+/// The `Content-Length` of a request or response, `None` when absent or malformed.
+pub fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whether the request is a speculative load the user may never look at.
+///
+/// Checks the headers browsers and Next.js actually send:
+///
+/// - `Sec-Purpose: prefetch`, or `prefetch;prerender`, from Chromium's speculation rules and
+///   `<link rel="prefetch">`
+/// - `Purpose: prefetch`, the older form of the same, and `X-Moz: prefetch` from Firefox
+/// - `Next-Router-Prefetch: 1`, sent by the App Router when `<Link>` prefetches a route
+/// - `X-Middleware-Prefetch: 1`, sent by the Pages Router when it prefetches data through
+///   middleware
+pub fn is_prefetch(headers: &HeaderMap) -> bool {
+    let value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    let purpose = |name: &str| {
+        value(name).is_some_and(|value| {
+            let first = value.split(';').next().unwrap_or_default().trim();
+            first.eq_ignore_ascii_case("prefetch")
+        })
+    };
+    purpose("sec-purpose")
+        || purpose("purpose")
+        || purpose("x-moz")
+        || value("next-router-prefetch") == Some("1")
+        || value("x-middleware-prefetch") == Some("1")
+}
+
+/// Fails when a `Content-Length` is declared but the body has a different size, e.g. for an
+/// upload that was cut off on the way.
+///
+/// Expects the framing to be normalized, see [`normalize_body_framing`].
+pub fn check_content_length(headers: &HeaderMap, actual: usize) -> Result<(), &'static str> {
+    match content_length(headers) {
+        Some(declared) if declared != actual as u64 => {
+            Err("Content-Length does not match the size of the body")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A `Link` header entry (RFC 8288) hinting a resource the page will need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    href: String,
+    rel: &'static str,
+    destination: Option<String>,
+    crossorigin: bool,
+}
+
+impl Link {
+    /// `rel=preload` for `href`, fetched as `destination`, e.g. `style`, `script` or `font`.
+    pub fn preload(href: impl Into<String>, destination: impl Into<String>) -> Self {
+        Link {
+            href: href.into(),
+            rel: "preload",
+            destination: Some(destination.into()),
+            crossorigin: false,
+        }
+    }
+
+    /// `rel=preconnect` to the origin `href`.
+    pub fn preconnect(href: impl Into<String>) -> Self {
+        Link {
+            href: href.into(),
+            rel: "preconnect",
+            destination: None,
+            crossorigin: false,
+        }
+    }
+
+    /// `rel=deprecation`, documentation on the deprecation of the resource (RFC 9745).
+    pub fn deprecation(href: impl Into<String>) -> Self {
+        Link {
+            href: href.into(),
+            rel: "deprecation",
+            destination: None,
+            crossorigin: false,
+        }
+    }
+
+    /// Fetches in CORS mode, required for fonts.
+    pub fn crossorigin(mut self) -> Self {
+        self.crossorigin = true;
+        self
+    }
+
+    /// The header value, `None` when the link contains characters a header cannot carry.
+    pub fn to_header_value(&self) -> Option<HeaderValue> {
+        let href: String = self
+            .href
+            .chars()
+            .filter(|c| !matches!(c, '<' | '>'))
+            .collect();
+        let mut value = format!("<{}>; rel={}", href, self.rel);
+        if let Some(destination) = &self.destination {
+            value.push_str("; as=");
+            value.push_str(destination);
+        }
+        if self.crossorigin {
+            value.push_str("; crossorigin");
+        }
+        HeaderValue::try_from(value).ok()
+    }
+}
+
+/// Request headers the response depends on, collected from every part of the pipeline.
+///
+/// Kept in the response extensions and written as a single, deduplicated and sorted `Vary`
+/// header when the response is sent, so contributions never overwrite each other.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VarySet(BTreeSet<String>);
+
+impl VarySet {
+    pub fn insert(&mut self, name: &str) {
+        self.0.insert(name.trim().to_ascii_lowercase());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+/// Adds `name` to the [`VarySet`] of a response.
+pub fn add_vary(extensions: &mut Extensions, name: &str) {
+    extensions.get_or_insert_with(VarySet::default).insert(name);
+}
+
+/// Folds existing `Vary` headers and the [`VarySet`] into one header.
+pub(crate) fn finalize_vary(headers: &mut HeaderMap, extensions: &mut Extensions) {
+    let mut vary = extensions.remove::<VarySet>().unwrap_or_default();
+    for value in headers.get_all(header::VARY) {
+        for name in value.to_str().unwrap_or_default().split(',') {
+            vary.insert(name);
+        }
+    }
+    vary.0.remove("");
+    if vary.0.is_empty() {
+        return;
+    }
+
+    // `*` already covers every other header
+    let value = match vary.0.contains("*") {
+        true => "*".to_owned(),
+        false => vary.iter().collect::<Vec<_>>().join(", "),
+    };
+    if let Ok(value) = HeaderValue::try_from(value) {
+        headers.insert(header::VARY, value);
+    }
+}
+
+/// Validates the body framing headers of an incoming request.
+///
+/// Repeated but identical `Content-Length` values are collapsed into one. Conflicting or
+/// malformed values, and `Content-Length` combined with `Transfer-Encoding`, are rejected:
+/// they are classic request smuggling vectors, since hops may disagree on where the body ends.
+/// The event always carries the complete, de-chunked body, so a lone `Transfer-Encoding` is
+/// dropped and the runtime sets `Content-Length` to the size of that body instead.
+pub fn normalize_body_framing(headers: &mut HeaderMap) -> Result<(), &'static str> {
+    let mut length = None;
+    for value in headers.get_all(header::CONTENT_LENGTH) {
+        let value = value.to_str().map_err(|_| "invalid Content-Length")?;
+        for part in value.split(',').map(str::trim) {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err("invalid Content-Length");
+            }
+            let part = part.trim_start_matches('0');
+            match length {
+                Some(length) if length != part => return Err("conflicting Content-Length values"),
+                _ => length = Some(part),
+            }
+        }
+    }
+
+    let length = length.map(|l| if l.is_empty() { "0" } else { l }.to_owned());
+    if headers.contains_key(header::TRANSFER_ENCODING) {
+        if length.is_some() {
+            return Err("both Content-Length and Transfer-Encoding are set");
+        }
+        headers.remove(header::TRANSFER_ENCODING);
+    }
+    if let Some(length) = length {
+        headers.insert(
+            header::CONTENT_LENGTH,
+            HeaderValue::try_from(length).map_err(|_| "invalid Content-Length")?,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        accepts_encoding, add_vary, check_content_length, check_header_size, content_disposition,
+        finalize_vary, is_prefetch, merge_headers, normalize_body_framing, remove_hop_by_hop, Link,
+        RedactedHeaders,
+    };
+    use lambda_http::http::{header, Extensions, HeaderMap};
+
+    fn framing(headers: &[(header::HeaderName, &str)]) -> Result<HeaderMap, &'static str> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, value.parse().unwrap());
+        }
+        normalize_body_framing(&mut map).map(|_| map)
+    }
+
+    #[test]
+    fn it_collapses_duplicate_content_lengths() {
+        let headers = framing(&[
+            (header::CONTENT_LENGTH, "42"),
+            (header::CONTENT_LENGTH, "42, 042"),
+        ])
+        .unwrap();
+        let lengths: Vec<_> = headers.get_all(header::CONTENT_LENGTH).iter().collect();
+        assert_eq!(lengths, ["42"]);
+
+        let headers = framing(&[(header::TRANSFER_ENCODING, "chunked")]).unwrap();
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn it_rejects_ambiguous_framing() {
+        assert!(framing(&[
+            (header::CONTENT_LENGTH, "42"),
+            (header::CONTENT_LENGTH, "7")
+        ])
+        .is_err());
+        assert!(framing(&[(header::CONTENT_LENGTH, "42,7")]).is_err());
+        assert!(framing(&[(header::CONTENT_LENGTH, "-1")]).is_err());
+        assert!(framing(&[(header::CONTENT_LENGTH, "")]).is_err());
+        assert!(framing(&[
+            (header::TRANSFER_ENCODING, "chunked"),
+            (header::CONTENT_LENGTH, "42"),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn it_merges_vary_contributions() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::VARY, "Accept-Language".parse().unwrap());
+        let mut extensions = Extensions::new();
+        // Compression, CORS, content negotiation and an explicit handler header
+        add_vary(&mut extensions, "Accept-Encoding");
+        add_vary(&mut extensions, "Origin");
+        add_vary(&mut extensions, "Accept");
+        add_vary(&mut extensions, "accept-encoding");
+        finalize_vary(&mut headers, &mut extensions);
+
+        let vary: Vec<_> = headers.get_all(header::VARY).iter().collect();
+        assert_eq!(vary, ["accept, accept-encoding, accept-language, origin"]);
+
+        headers.append(header::VARY, "*".parse().unwrap());
+        finalize_vary(&mut headers, &mut Extensions::new());
+        assert_eq!(headers[header::VARY], "*");
+    }
+
+    #[test]
+    fn it_limits_header_sizes() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "a".repeat(100).parse().unwrap());
+        headers.insert(header::ACCEPT, "*/*".parse().unwrap());
+        assert!(check_header_size(&headers, 200, 200).is_ok());
+        assert_eq!(
+            check_header_size(&headers, 50, 200),
+            Err("request header too large")
+        );
+        assert_eq!(
+            check_header_size(&headers, 200, 110),
+            Err("request headers too large")
+        );
+    }
+
+    #[test]
+    fn it_redacts_sensitive_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        headers.insert("x-api-key", "key".parse().unwrap());
+        headers.insert(header::ACCEPT, "*/*".parse().unwrap());
+        let extra = [header::HeaderName::from_static("x-api-key")];
+        let logged = format!(
+            "{:?}",
+            RedactedHeaders {
+                headers: &headers,
+                extra: &extra,
+            }
+        );
+        assert_eq!(
+            logged,
+            r#"{"authorization": <redacted:13 bytes>, "x-api-key": <redacted:3 bytes>, "accept": "*/*"}"#
+        );
+    }
+
+    #[test]
+    fn it_negotiates_content_codings() {
+        let mut headers = HeaderMap::new();
+        assert!(accepts_encoding(&headers, "identity"));
+        assert!(!accepts_encoding(&headers, "br"));
+
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            "gzip;q=0.8, BR, deflate;q=0".parse().unwrap(),
+        );
+        assert!(accepts_encoding(&headers, "br"));
+        assert!(accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "deflate"));
+        assert!(!accepts_encoding(&headers, "zstd"));
+        assert!(accepts_encoding(&headers, "identity"));
+
+        headers.insert(header::ACCEPT_ENCODING, "*;q=0, gzip".parse().unwrap());
+        assert!(accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "identity"));
+
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            "gzip;Q=0, br; q = 0, deflate".parse().unwrap(),
+        );
+        assert!(!accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "br"));
+        assert!(accepts_encoding(&headers, "deflate"));
+    }
+
+    #[test]
+    fn it_encodes_download_filenames() {
+        assert_eq!(
+            content_disposition("attachment", "report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("inline", "say \"hi\"\r\n.txt"),
+            "inline; filename=\"say _hi_.txt\"; filename*=UTF-8''say%20%22hi%22.txt"
+        );
+        assert_eq!(
+            content_disposition("attachment", "Übersicht €.csv"),
+            "attachment; filename=\"_bersicht _.csv\"; \
+             filename*=UTF-8''%C3%9Cbersicht%20%E2%82%AC.csv"
+        );
+    }
+
+    #[test]
+    fn it_removes_hop_by_hop_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONNECTION,
+            "keep-alive, x-upstream-id".parse().unwrap(),
+        );
+        headers.insert("keep-alive", "timeout=5".parse().unwrap());
+        headers.insert("x-upstream-id", "1".parse().unwrap());
+        headers.insert(header::TRANSFER_ENCODING, "chunked".parse().unwrap());
+        headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        remove_hop_by_hop(&mut headers);
+
+        let names: Vec<_> = headers.keys().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["content-type"]);
+    }
+
+    #[test]
+    fn it_appends_cookies_and_replaces_singletons() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CACHE_CONTROL, "no-store".parse().unwrap());
+        headers.insert(header::SET_COOKIE, "a=1".parse().unwrap());
+
+        let mut other = HeaderMap::new();
+        other.insert(header::CACHE_CONTROL, "max-age=60".parse().unwrap());
+        other.append(header::SET_COOKIE, "b=2".parse().unwrap());
+        other.append(header::SET_COOKIE, "c=3".parse().unwrap());
+        merge_headers(&mut headers, other);
+
+        assert_eq!(headers[header::CACHE_CONTROL], "max-age=60");
+        let cookies: Vec<_> = headers.get_all(header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2", "c=3"]);
+    }
+
+    #[test]
+    fn it_formats_link_hints() {
+        let style = Link::preload("/app.css", "style")
+            .to_header_value()
+            .unwrap();
+        assert_eq!(style, "</app.css>; rel=preload; as=style");
+        let font = Link::preload("/inter.woff2", "font").crossorigin();
+        assert_eq!(
+            font.to_header_value().unwrap(),
+            "</inter.woff2>; rel=preload; as=font; crossorigin"
+        );
+        let origin = Link::preconnect("https://cdn.example.com");
+        assert_eq!(
+            origin.to_header_value().unwrap(),
+            "<https://cdn.example.com>; rel=preconnect"
+        );
+        let docs = Link::deprecation("https://example.com/docs/step-v4");
+        assert_eq!(
+            docs.to_header_value().unwrap(),
+            "<https://example.com/docs/step-v4>; rel=deprecation"
+        );
+    }
+
+    #[test]
+    fn it_checks_declared_body_sizes() {
+        let mut headers = HeaderMap::new();
+        assert!(check_content_length(&headers, 5).is_ok());
+        headers.insert(header::CONTENT_LENGTH, "5".parse().unwrap());
+        assert!(check_content_length(&headers, 5).is_ok());
+        assert!(check_content_length(&headers, 3).is_err());
+    }
+
+    #[test]
+    fn it_detects_prefetches() {
+        let prefetch = |name: &str, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+            is_prefetch(&headers)
+        };
+        assert!(prefetch("sec-purpose", "prefetch"));
+        assert!(prefetch("sec-purpose", "prefetch;prerender"));
+        assert!(prefetch("purpose", "Prefetch"));
+        assert!(prefetch("next-router-prefetch", "1"));
+        assert!(prefetch("x-middleware-prefetch", "1"));
+        assert!(!prefetch("sec-purpose", "prerender"));
+        assert!(!prefetch("next-router-prefetch", "0"));
+        assert!(!is_prefetch(&HeaderMap::new()));
+    }
+}
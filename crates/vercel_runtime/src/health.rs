@@ -0,0 +1,199 @@
+//! Health check endpoint built from a registry of async dependency checks.
+//!
+//! ```no_run
+//! # use std::sync::LazyLock;
+//! # use vercel_runtime::{health::HealthCheck, Body, Error, Request, Response};
+//! static HEALTH: LazyLock<HealthCheck> = LazyLock::new(|| {
+//!     HealthCheck::new().add("kv", || async { Ok(()) })
+//! });
+//!
+//! pub async fn handler(_req: Request) -> Result<Response<Body>, Error> {
+//!     HEALTH.respond().await
+//! }
+//! ```
+use crate::Body;
+use futures_util::future::{join_all, BoxFuture};
+use lambda_http::{
+    http::{header, StatusCode},
+    Error, Response,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type Check = Arc<dyn Fn() -> BoxFuture<'static, Result<(), Error>> + Send + Sync>;
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CheckStatus {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of all checks, the body of the health response.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub checks: BTreeMap<String, CheckStatus>,
+}
+
+/// A registry of named dependency checks.
+pub struct HealthCheck {
+    checks: Vec<(String, Check)>,
+    timeout: Duration,
+    cache_for: Duration,
+    cached: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        HealthCheck {
+            checks: Vec::new(),
+            timeout: Duration::from_secs(2),
+            cache_for: Duration::from_secs(5),
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+impl HealthCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a check, it fails by returning an error or exceeding the timeout.
+    pub fn add<F, Fut>(mut self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.checks
+            .push((name.into(), Arc::new(move || Box::pin(check()))));
+        self
+    }
+
+    /// Time each check may take, 2 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long a report is reused to spare dependencies from frequent probes, 5 seconds by
+    /// default.
+    pub fn cache_for(mut self, cache_for: Duration) -> Self {
+        self.cache_for = cache_for;
+        self
+    }
+
+    /// Runs all checks concurrently, or returns the cached report.
+    pub async fn report(&self) -> HealthReport {
+        if let Some((at, report)) = self.cached.lock().ok().and_then(|c| c.clone()) {
+            if at.elapsed() < self.cache_for {
+                return report;
+            }
+        }
+
+        let results = join_all(self.checks.iter().map(|(name, check)| async move {
+            let started = Instant::now();
+            let error = match tokio::time::timeout(self.timeout, check()).await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some(format!("timed out after {:?}", self.timeout)),
+            };
+            let status = CheckStatus {
+                healthy: error.is_none(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                error,
+            };
+            (name.clone(), status)
+        }))
+        .await;
+
+        let report = HealthReport {
+            healthy: results.iter().all(|(_, s)| s.healthy),
+            checks: results.into_iter().collect(),
+        };
+        if let Ok(mut cached) = self.cached.lock() {
+            *cached = Some((Instant::now(), report.clone()));
+        }
+        report
+    }
+
+    /// `200` when every check passed, `503` otherwise, with the [`HealthReport`] as JSON.
+    pub async fn respond(&self) -> Result<Response<Body>, Error> {
+        let report = self.report().await;
+        let status = match report.healthy {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        Ok(Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::Text(serde_json::to_string(&report)?))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HealthCheck;
+    use lambda_http::{http::StatusCode, Error};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn it_reports_failures_and_timeouts() {
+        let health = HealthCheck::new()
+            .timeout(Duration::from_millis(20))
+            .add("mongo", || async { Ok(()) })
+            .add("kv", || async { Err(Error::from("connection refused")) })
+            .add("slow", || async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            });
+
+        let response = health.respond().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let report = health.report().await;
+        assert!(report.checks["mongo"].healthy);
+        assert_eq!(
+            report.checks["kv"].error.as_deref(),
+            Some("connection refused")
+        );
+        assert!(report.checks["slow"]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("timed out"));
+    }
+
+    #[tokio::test]
+    async fn it_caches_reports() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let health = HealthCheck::new().add("kv", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        });
+
+        assert_eq!(health.respond().await.unwrap().status(), StatusCode::OK);
+        health.respond().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let health = health.cache_for(Duration::ZERO);
+        health.report().await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
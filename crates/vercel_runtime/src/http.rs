@@ -1,5 +1,10 @@
-use lambda_http::{http::StatusCode, Body, Error, Response};
+use crate::{Body, IntoResponse};
+use lambda_http::{
+    http::{header, response::Builder, StatusCode},
+    Error, Response,
+};
 use serde::Serialize;
+use std::fmt::Display;
 
 pub fn ok(val: impl Serialize) -> Result<Response<Body>, Error> {
     Ok(Response::builder()
@@ -8,6 +13,103 @@ pub fn ok(val: impl Serialize) -> Result<Response<Body>, Error> {
         .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
 }
 
+/// A JSON response with the given status.
+pub fn from_value(status: StatusCode, value: serde_json::Value) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::try_from(value)?)?)
+}
+
+/// An indented JSON response for humans, e.g. debug routes. Prefer [`from_value`] otherwise.
+pub fn json_pretty<T: Serialize + ?Sized>(
+    status: StatusCode,
+    value: &T,
+) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::Text(serde_json::to_string_pretty(value)?))?)
+}
+
+/// A builder for a numeric status, e.g. one received from an upstream service.
+///
+/// Fails for codes outside of `100..=599` instead of falling back to a default.
+pub fn builder_status_u16(code: u16) -> Result<Builder, Error> {
+    if !(100..=599).contains(&code) {
+        return Err(Error::from(format!("invalid status code {}", code)));
+    }
+    Ok(Response::builder().status(StatusCode::from_u16(code)?))
+}
+
+/// Relays an upstream response with a numeric status, see [`builder_status_u16`].
+pub fn from_upstream(code: u16, body: impl Into<Body>) -> Result<Response<Body>, Error> {
+    Ok(builder_status_u16(code)?.body(body.into())?)
+}
+
+/// Relays an upstream `reqwest` response, streaming its body chunk by chunk.
+///
+/// Status and headers are copied except for hop-by-hop headers, see
+/// [`crate::headers::remove_hop_by_hop`]. An error while reading the upstream body ends the
+/// stream with that error.
+#[cfg(feature = "reqwest")]
+pub fn stream_reqwest(upstream: reqwest::Response) -> Result<Response<Body>, Error> {
+    let mut builder = Response::builder().status(upstream.status());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = upstream.headers().clone();
+        crate::headers::remove_hop_by_hop(headers);
+    }
+    Ok(builder.body(Body::from_stream(upstream.bytes_stream()))?)
+}
+
+pub fn no_content() -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::Empty)?)
+}
+
+/// `303 See Other`, the client follows up with a `GET` to `location`.
+///
+/// Prefer it after handling a `POST` from a browser (post/redirect/get), so the request body is
+/// not sent again.
+pub fn see_other(location: &str) -> Result<Response<Body>, Error> {
+    redirect(StatusCode::SEE_OTHER, location)
+}
+
+/// `307 Temporary Redirect`, the client repeats the same method and body against `location`.
+///
+/// Use it to hand a request over to another handler, e.g. chaining steps that each need the
+/// original payload.
+pub fn temporary_redirect(location: &str) -> Result<Response<Body>, Error> {
+    redirect(StatusCode::TEMPORARY_REDIRECT, location)
+}
+
+/// `308 Permanent Redirect`, like [`temporary_redirect`] but cacheable by the client.
+///
+/// Use it for canonical URLs (scheme, host, trailing slash), as the [`crate::bundled_api`]
+/// router does for [`crate::TrailingSlash::Redirect`]. How the redirect statuses differ:
+///
+/// | Status | Permanent | Method and body on the next request |
+/// |--------|-----------|-------------------------------------|
+/// | `301`  | yes       | browsers turn `POST` into `GET`     |
+/// | `302`  | no        | browsers turn `POST` into `GET`     |
+/// | `303`  | no        | always `GET` ([`see_other`])        |
+/// | `307`  | no        | kept ([`temporary_redirect`])       |
+/// | `308`  | yes       | kept                                |
+///
+/// `301` and `302` are left out on purpose, `308` and `307` say the same without the method
+/// change.
+pub fn permanent_redirect(location: &str) -> Result<Response<Body>, Error> {
+    redirect(StatusCode::PERMANENT_REDIRECT, location)
+}
+
+fn redirect(status: StatusCode, location: &str) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(status)
+        .header(header::LOCATION, location)
+        .body(Body::Empty)?)
+}
+
 pub fn bad_request(val: impl Serialize) -> Result<Response<Body>, Error> {
     Ok(Response::builder()
         .status(StatusCode::BAD_REQUEST)
@@ -29,9 +131,157 @@ pub fn unauthorized(val: impl Serialize) -> Result<Response<Body>, Error> {
         .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
 }
 
+pub fn forbidden(val: impl Serialize) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("content-type", "application/json")
+        .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
+}
+
+/// `412 Precondition Failed`, see [`crate::conditional`].
+pub fn precondition_failed(val: impl Serialize) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::PRECONDITION_FAILED)
+        .header("content-type", "application/json")
+        .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
+}
+
+/// `508 Loop Detected`, see [`crate::steps`].
+pub fn loop_detected(val: impl Serialize) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::LOOP_DETECTED)
+        .header("content-type", "application/json")
+        .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
+}
+
 pub fn internal_server_error(val: impl Serialize) -> Result<Response<Body>, Error> {
     Ok(Response::builder()
         .status(StatusCode::INTERNAL_SERVER_ERROR)
         .header("content-type", "application/json")
         .body(Body::Text(serde_json::to_string(&val).unwrap()))?)
 }
+
+/// Outputs of the nodes that completed, along with the errors of those that failed.
+///
+/// Lets a workflow return its progress instead of a bare `500` when a single node fails. The
+/// body is `{"data": [...], "partial": bool, "errors": [{"node": ..., "error": ...}]}`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PartialResult {
+    data: Vec<serde_json::Value>,
+    partial: bool,
+    errors: Vec<NodeError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeError {
+    pub node: String,
+    pub error: String,
+}
+
+impl PartialResult {
+    pub fn new(data: Vec<serde_json::Value>) -> Self {
+        PartialResult {
+            data,
+            ..Default::default()
+        }
+    }
+
+    /// Records the failure of `node`, which marks the result as partial.
+    pub fn with_error(mut self, node: impl Into<String>, error: impl Display) -> Self {
+        self.partial = true;
+        self.errors.push(NodeError {
+            node: node.into(),
+            error: error.to_string(),
+        });
+        self
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+}
+
+/// `207 Multi-Status` when some node failed, `200` otherwise.
+impl IntoResponse for PartialResult {
+    fn into_response(self) -> Response<Body> {
+        let status = match self.partial {
+            true => StatusCode::MULTI_STATUS,
+            false => StatusCode::OK,
+        };
+        (status, self).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_upstream, PartialResult};
+    use crate::{Body, IntoResponse};
+    use lambda_http::http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn it_validates_upstream_status_codes() {
+        let response = from_upstream(418, "teapot").unwrap();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert!(from_upstream(99, ()).is_err());
+        assert!(from_upstream(600, ()).is_err());
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn it_streams_upstream_responses() {
+        let upstream = lambda_http::http::Response::builder()
+            .status(StatusCode::CREATED)
+            .header("connection", "close")
+            .header("content-type", "text/plain")
+            .body("upstream body")
+            .unwrap();
+        let response = super::stream_reqwest(reqwest::Response::from(upstream)).unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert!(!response.headers().contains_key("connection"));
+        assert!(response.body().is_stream());
+        let body = response.into_body().into_bytes().await.unwrap();
+        assert_eq!(body, "upstream body".as_bytes());
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn it_keeps_content_type_parameters_verbatim() {
+        let content_type = "multipart/mixed; boundary=\"part-7f3a\"; charset=UTF-8";
+        let body = "--part-7f3a\r\n\r\nfirst\r\n--part-7f3a--\r\n";
+        let upstream = lambda_http::http::Response::builder()
+            .header("content-type", content_type)
+            .body(body)
+            .unwrap();
+        let response = super::stream_reqwest(reqwest::Response::from(upstream)).unwrap();
+
+        assert_eq!(response.headers()["content-type"], content_type);
+        let proxied = response.into_body().into_bytes().await.unwrap();
+        assert_eq!(proxied, body.as_bytes());
+    }
+
+    #[test]
+    fn it_keeps_completed_nodes_on_failure() {
+        let response = PartialResult::new(vec![json!({"node": "fetch"})])
+            .with_error("summarize", "upstream timed out")
+            .into_response();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "data": [{"node": "fetch"}],
+                "partial": true,
+                "errors": [{"node": "summarize", "error": "upstream timed out"}],
+            })
+        );
+
+        let response = PartialResult::new(Vec::new()).into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,53 @@
+//! A process wide `reqwest` client, reused across invocations to keep connections warm.
+//!
+//! Configure it once at startup, before the runtime starts:
+//!
+//! ```no_run
+//! # use vercel_runtime::{http_client, Error};
+//! # fn main() -> Result<(), Error> {
+//! http_client::init(|builder| Ok(builder.user_agent("my-function")))?;
+//! # Ok(())
+//! # }
+//! ```
+use lambda_http::Error;
+use reqwest::{Client, ClientBuilder};
+use std::sync::OnceLock;
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Builds the shared client from `configure`.
+///
+/// Fails when `configure` or the build fails, so a cold start with a broken setup fails loudly,
+/// and when the client was already initialized or used.
+pub fn init(
+    configure: impl FnOnce(ClientBuilder) -> Result<ClientBuilder, Error>,
+) -> Result<(), Error> {
+    if CLIENT.get().is_some() {
+        return Err(Error::from(
+            "the HTTP client was already initialized or used",
+        ));
+    }
+    let client = configure(Client::builder())?.build()?;
+    CLIENT
+        .set(client)
+        .map_err(|_| Error::from("the HTTP client was already initialized or used"))
+}
+
+/// The shared client, created with the default settings when [`init`] was not called.
+pub fn http_client() -> &'static Client {
+    CLIENT.get_or_init(Client::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{http_client, init};
+    use lambda_http::Error;
+
+    #[test]
+    fn it_initializes_the_client_once() {
+        assert!(init(|_| Err(Error::from("invalid root certificate"))).is_err());
+        init(|builder| Ok(builder.user_agent("test"))).unwrap();
+        assert!(init(Ok).is_err());
+        assert!(std::ptr::eq(http_client(), http_client()));
+    }
+}
@@ -0,0 +1,139 @@
+//! Conversions from handler return values into responses, see [`IntoResponse`].
+use crate::Body;
+use lambda_http::http::{header, StatusCode};
+use lambda_http::Response;
+use serde::Serialize;
+use tracing::error;
+
+/// A value [`crate::run`] can send as the response.
+///
+/// Lets handlers return e.g. `(StatusCode::CREATED, item)` instead of building the response:
+///
+/// ```no_run
+/// # use vercel_runtime::{run, Error, StatusCode};
+/// # async fn example() -> Result<(), Error> {
+/// run(|_req| async { Ok((StatusCode::CREATED, serde_json::json!({ "id": 1 }))) }).await
+/// # }
+/// ```
+pub trait IntoResponse {
+    fn into_response(self) -> Response<Body>;
+}
+
+impl IntoResponse for Response<Body> {
+    fn into_response(self) -> Response<Body> {
+        self
+    }
+}
+
+/// An empty response with the status.
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response<Body> {
+        let mut response = Response::new(Body::Empty);
+        *response.status_mut() = self;
+        response
+    }
+}
+
+/// The value as JSON with the status, or a `500` when it does not serialize.
+impl<T: Serialize> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Response<Body> {
+        let (status, value) = self;
+        match serde_json::to_string(&value) {
+            Ok(json) => with_content_type(status, Body::Text(json), "application/json"),
+            Err(e) => {
+                error!("Could not serialize the response body: {}", e);
+                let body = serde_json::json!({ "error": "response serialization failed" });
+                with_content_type(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Body::Text(body.to_string()),
+                    "application/json",
+                )
+            }
+        }
+    }
+}
+
+/// A `200` JSON response.
+impl IntoResponse for serde_json::Value {
+    fn into_response(self) -> Response<Body> {
+        with_content_type(
+            StatusCode::OK,
+            Body::Text(self.to_string()),
+            "application/json",
+        )
+    }
+}
+
+/// A `200` plain text response.
+impl IntoResponse for String {
+    fn into_response(self) -> Response<Body> {
+        with_content_type(
+            StatusCode::OK,
+            Body::Text(self),
+            "text/plain; charset=utf-8",
+        )
+    }
+}
+
+impl IntoResponse for &str {
+    fn into_response(self) -> Response<Body> {
+        self.to_owned().into_response()
+    }
+}
+
+/// Either side, so handlers can answer failures with a response of their own.
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            Ok(response) => response.into_response(),
+            Err(response) => response.into_response(),
+        }
+    }
+}
+
+fn with_content_type(status: StatusCode, body: Body, content_type: &'static str) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(content_type),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntoResponse;
+    use crate::Body;
+    use lambda_http::http::StatusCode;
+    use serde_json::json;
+
+    fn text(body: &Body) -> &str {
+        let Body::Text(text) = body else {
+            panic!("expected a text body");
+        };
+        text
+    }
+
+    #[test]
+    fn it_converts_handler_return_values() {
+        let response = StatusCode::ACCEPTED.into_response();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(matches!(response.body(), Body::Empty));
+
+        let response = (StatusCode::CREATED, json!({ "id": 1 })).into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        assert_eq!(text(response.body()), r#"{"id":1}"#);
+
+        let response = "hello".into_response();
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(text(response.body()), "hello");
+
+        let failed: Result<String, _> = Err((StatusCode::NOT_FOUND, json!({ "error": "gone" })));
+        assert_eq!(failed.into_response().status(), StatusCode::NOT_FOUND);
+    }
+}
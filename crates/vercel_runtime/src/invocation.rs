@@ -0,0 +1,129 @@
+use lambda_runtime::Context;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio_util::sync::CancellationToken;
+
+/// How long before the hard Lambda deadline the deadline token is cancelled.
+pub const DEADLINE_MARGIN: Duration = Duration::from_millis(500);
+
+// Functions process one invocation at a time, so a single watcher covers the current invocation
+static DEADLINE_WATCHER: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Per-invocation metadata attached to every request by the runtime.
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    pub request_id: String,
+    pub deadline: Option<SystemTime>,
+    pub deadline_token: CancellationToken,
+    /// When the runtime decoded the event, on the monotonic clock.
+    pub received_at: Instant,
+}
+
+impl Invocation {
+    pub fn new(context: &Context) -> Self {
+        let deadline = match context.deadline {
+            0 => None,
+            _ => Some(context.deadline()),
+        };
+
+        let invocation = Invocation {
+            request_id: context.request_id.clone(),
+            deadline,
+            deadline_token: CancellationToken::new(),
+            received_at: Instant::now(),
+        };
+        invocation.arm_deadline_token();
+        invocation
+    }
+
+    /// Time left until the hard deadline, if one is known.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+
+    fn arm_deadline_token(&self) {
+        let Some(remaining) = self.remaining() else {
+            return;
+        };
+        // Requests decoded outside of a tokio runtime (e.g. in unit tests) have no timer
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let token = self.deadline_token.clone();
+        let wait = remaining.saturating_sub(DEADLINE_MARGIN);
+        // Stopped separately so that ending the invocation does not cancel the handler's token
+        let stop = CancellationToken::new();
+        if let Ok(mut watcher) = DEADLINE_WATCHER.lock() {
+            if let Some(previous) = watcher.replace(stop.clone()) {
+                previous.cancel();
+            }
+        }
+        handle.spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => token.cancel(),
+                _ = token.cancelled() => {}
+                _ = stop.cancelled() => {}
+            }
+        });
+    }
+}
+
+/// Ends the deadline watcher of the current invocation, so warm instances do not pile up one
+/// sleeping task per request.
+pub(crate) fn stop_deadline_watcher() {
+    if let Some(stop) = DEADLINE_WATCHER.lock().ok().and_then(|mut w| w.take()) {
+        stop.cancel();
+    }
+}
+
+/// Resolves once the client went away, see [`crate::RequestExt::disconnected`].
+///
+/// The Lambda invocation model does not report client disconnects to the function, so for now
+/// this never resolves. Streaming handlers can watch [`crate::streaming::BodySender::is_closed`]
+/// instead.
+#[derive(Debug, Default)]
+pub struct Disconnected(());
+
+impl Future for Disconnected {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<()> {
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stop_deadline_watcher, Invocation, DEADLINE_MARGIN};
+    use lambda_runtime::Context;
+    use std::time::{Duration, SystemTime};
+
+    fn invocation(remaining: Duration) -> Invocation {
+        let deadline = SystemTime::now() + DEADLINE_MARGIN + remaining;
+        let mut context = Context::default();
+        context.deadline = deadline
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        Invocation::new(&context)
+    }
+
+    #[tokio::test]
+    async fn it_stops_the_deadline_watcher_with_the_invocation() {
+        let expiring = invocation(Duration::from_millis(50));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(expiring.deadline_token.is_cancelled());
+
+        let finished = invocation(Duration::from_millis(50));
+        stop_deadline_watcher();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(!finished.deadline_token.is_cancelled());
+    }
+}
@@ -0,0 +1,125 @@
+//! Strict JSON request bodies with a configurable `400` response.
+//!
+//! [`crate::RequestExt::expect_json`] answers malformed bodies with the built-in envelope
+//! `{"error": "invalid JSON body", "message": "...", "line": 1, "column": 7}`, or with the
+//! response built by [`crate::RuntimeConfig::json_error_response`] when one is configured.
+use crate::Body;
+use lambda_http::{http::StatusCode, Request, Response};
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+/// Builds the response for a body that failed to parse.
+pub type JsonErrorResponse = fn(&JsonError) -> Response<Body>;
+
+/// Why a JSON body was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError {
+    pub message: String,
+    /// Position of the error in the body, `0` when it does not apply (e.g. an empty body).
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError {
+            message: error.to_string(),
+            line: error.line(),
+            column: error.column(),
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// The response hook configured for the runtime, kept in the request extensions.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct JsonErrorHook(pub JsonErrorResponse);
+
+/// Parses `body` as JSON into `T`.
+pub fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, JsonError> {
+    if body.is_empty() {
+        return Err(JsonError {
+            message: "request body is empty".to_owned(),
+            line: 0,
+            column: 0,
+        });
+    }
+    Ok(serde_json::from_slice(body)?)
+}
+
+/// The built-in `400` envelope, see the module docs.
+pub fn default_response(error: &JsonError) -> Response<Body> {
+    let body = serde_json::json!({
+        "error": "invalid JSON body",
+        "message": error.message,
+        "line": error.line,
+        "column": error.column,
+    });
+    let mut response = Response::new(Body::Text(body.to_string()));
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response
+        .headers_mut()
+        .insert("content-type", "application/json".parse().unwrap());
+    response
+}
+
+// The response is handed straight back to the runtime, boxing it would only add noise
+#[allow(clippy::result_large_err)]
+pub(crate) fn expect_json<T: DeserializeOwned>(req: &Request) -> Result<T, Response<Body>> {
+    parse_json(req.body()).map_err(|error| {
+        let respond = req
+            .extensions()
+            .get::<JsonErrorHook>()
+            .map_or(default_response as JsonErrorResponse, |hook| hook.0);
+        respond(&error)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expect_json, JsonError, JsonErrorHook};
+    use crate::Body;
+    use lambda_http::{http::StatusCode, Request, Response};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Input {
+        name: String,
+    }
+
+    fn field_errors(error: &JsonError) -> Response<Body> {
+        let body = serde_json::json!([{ "field": null, "message": error.message }]);
+        let mut response = Response::new(Body::Text(body.to_string()));
+        *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+        response
+    }
+
+    #[test]
+    fn it_maps_parse_errors_to_responses() {
+        let req = Request::new(r#"{"name": "a"}"#.into());
+        assert!(expect_json::<Input>(&req).is_ok());
+
+        let req = Request::new(r#"{"name": 1}"#.into());
+        let response = expect_json::<Input>(&req).unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["error"], "invalid JSON body");
+        assert_eq!(body["column"], 10);
+
+        let mut req = Request::new("".into());
+        req.extensions_mut().insert(JsonErrorHook(field_errors));
+        let response = expect_json::<Input>(&req).unwrap_err();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
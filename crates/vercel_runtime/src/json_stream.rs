@@ -0,0 +1,274 @@
+use crate::streaming::{self, BodySender, FlushPolicy, HeadersMut};
+use crate::Body;
+use futures_util::StreamExt;
+use lambda_http::http::{header, response::Builder, Response};
+use lambda_http::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::marker::PhantomData;
+
+/// Record separator that starts every record of a JSON text sequence.
+const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// Streams a `{"<key>":[...]}` envelope, writing each element as soon as it is available.
+///
+/// ```no_run
+/// # use vercel_runtime::{json_stream::JsonArrayWriter, streaming, Error};
+/// # async fn example() -> Result<(), Error> {
+/// let (sender, body) = streaming::channel();
+/// tokio::spawn(async move {
+///     let mut writer = JsonArrayWriter::new(sender);
+///     writer.write(&serde_json::json!({ "step": 0 })).await?;
+///     writer.finish().await
+/// });
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonArrayWriter {
+    sender: BodySender,
+    prefix: String,
+    started: bool,
+}
+
+impl JsonArrayWriter {
+    /// Writes elements into a `{"data":[...]}` envelope.
+    pub fn new(sender: BodySender) -> Self {
+        Self::with_key(sender, "data")
+    }
+
+    pub fn with_key(sender: BodySender, key: &str) -> Self {
+        let key = serde_json::to_string(key).expect("strings always serialize");
+        JsonArrayWriter {
+            sender,
+            prefix: format!("{{{}:[", key),
+            started: false,
+        }
+    }
+
+    /// Serializes and sends a single array element.
+    ///
+    /// Serialization errors are returned before anything is written, so the envelope stays valid.
+    pub async fn write<T: Serialize + ?Sized>(&mut self, element: &T) -> Result<(), Error> {
+        let element = serde_json::to_vec(element)?;
+
+        let mut chunk = Vec::with_capacity(self.prefix.len() + element.len() + 1);
+        if self.started {
+            chunk.push(b',');
+        } else {
+            chunk.extend_from_slice(self.prefix.as_bytes());
+        }
+        chunk.extend_from_slice(&element);
+
+        self.sender.send(chunk).await?;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Closes the array and the envelope. Must be called for the output to be valid JSON.
+    pub async fn finish(mut self) -> Result<(), Error> {
+        let closing = match self.started {
+            true => "]}".to_owned(),
+            false => format!("{}]}}", self.prefix),
+        };
+        self.sender.send(closing).await
+    }
+}
+
+/// Writer for an `application/json-seq` (RFC 7464) response.
+///
+/// Every record is sent as `0x1E <json> \n` as soon as it is written.
+#[derive(Debug)]
+pub struct JsonSeqResponse {
+    sender: BodySender,
+}
+
+impl JsonSeqResponse {
+    /// Creates the writer and the response to return from the handler.
+    ///
+    /// Records are batched with [`FlushPolicy::BATCHED`], see
+    /// [`JsonSeqResponse::set_flush_policy`].
+    pub fn new(builder: Builder) -> Result<(Self, Response<Body>), Error> {
+        let (mut sender, body) = streaming::channel();
+        sender.set_flush_policy(FlushPolicy::BATCHED);
+        let response = builder
+            .header(header::CONTENT_TYPE, "application/json-seq")
+            .extension(sender.attach_headers())
+            .body(body)?;
+        Ok((JsonSeqResponse { sender }, response))
+    }
+
+    /// Serializes and sends a single record.
+    pub async fn send<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        let mut chunk = vec![RECORD_SEPARATOR];
+        serde_json::to_writer(&mut chunk, record)?;
+        chunk.push(b'\n');
+        self.sender.send(chunk).await
+    }
+
+    /// Headers to add to the response until the first record is sent, see
+    /// [`crate::sse::SseResponse::headers_mut`].
+    pub fn headers_mut(&self) -> Result<HeadersMut<'_>, Error> {
+        self.sender.headers_mut()
+    }
+
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        self.sender.set_flush_policy(policy);
+    }
+}
+
+/// Streams `values` as newline delimited JSON (`application/x-ndjson`), one record per
+/// element, e.g. to dump large intermediate results from a debug route.
+///
+/// With `fields` given, every record only keeps those dotted paths, so `["id", "output.text"]`
+/// turns `{"id": 1, "output": {"text": "hi", "tokens": 3}}` into
+/// `{"id": 1, "output": {"text": "hi"}}`. Paths only descend into objects, missing ones are
+/// left out.
+pub fn ndjson_response(values: Vec<Value>, fields: &[&str]) -> Result<Response<Body>, Error> {
+    let fields: Vec<Vec<String>> = fields
+        .iter()
+        .map(|field| field.split('.').map(str::to_owned).collect())
+        .collect();
+    let records = futures_util::stream::iter(values).map(move |value| {
+        let mut line = match fields.is_empty() {
+            true => serde_json::to_vec(&value)?,
+            false => serde_json::to_vec(&project(&value, &fields))?,
+        };
+        line.push(b'\n');
+        Ok::<_, Error>(line)
+    });
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from_stream(records))?)
+}
+
+fn project(value: &Value, fields: &[Vec<String>]) -> Value {
+    let mut out = Value::Object(Map::new());
+    for path in fields {
+        let Some(selected) = path
+            .iter()
+            .try_fold(value, |value, key| value.as_object()?.get(key))
+        else {
+            continue;
+        };
+        let mut target = &mut out;
+        for key in &path[..path.len() - 1] {
+            let Value::Object(object) = target else {
+                break;
+            };
+            target = object
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        if let Value::Object(object) = target {
+            object.insert(path[path.len() - 1].clone(), selected.clone());
+        }
+    }
+    out
+}
+
+/// Records of a JSON text sequence, see [`parse_json_seq`].
+#[derive(Debug)]
+pub struct JsonSeqRecords<'a, T> {
+    records: std::slice::Split<'a, u8, fn(&u8) -> bool>,
+    _record: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Iterator for JsonSeqRecords<'_, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = self.records.next()?.trim_ascii();
+            if record.is_empty() {
+                continue;
+            }
+            // A malformed (e.g. truncated) record does not affect the ones after it
+            return Some(serde_json::from_slice(record).map_err(Error::from));
+        }
+    }
+}
+
+/// Parses an `application/json-seq` body record by record.
+pub fn parse_json_seq<T: DeserializeOwned>(body: &[u8]) -> JsonSeqRecords<'_, T> {
+    JsonSeqRecords {
+        records: body.split(|b| *b == RECORD_SEPARATOR),
+        _record: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ndjson_response, parse_json_seq, JsonArrayWriter, JsonSeqResponse};
+    use crate::{streaming, Body};
+    use futures_util::StreamExt;
+    use lambda_http::Response;
+    use serde_json::{json, Value};
+
+    async fn collect(body: Body) -> String {
+        let Body::Stream(mut stream) = body else {
+            panic!("expected a streaming body");
+        };
+        let mut out = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            out.extend_from_slice(&chunk.unwrap());
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_writes_empty_envelope() {
+        let (sender, body) = streaming::channel();
+        JsonArrayWriter::new(sender).finish().await.unwrap();
+        assert_eq!(collect(body).await, r#"{"data":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn it_writes_elements() {
+        let (sender, body) = streaming::channel();
+        let mut writer = JsonArrayWriter::with_key(sender, "we\"ird");
+        writer.write(&json!({ "a": "x\ny" })).await.unwrap();
+        writer.write(&json!(2)).await.unwrap();
+        writer.finish().await.unwrap();
+
+        let out = collect(body).await;
+        assert_eq!(out, r#"{"we\"ird":[{"a":"x\ny"},2]}"#);
+        serde_json::from_str::<serde_json::Value>(&out).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_json_seq() {
+        let (mut writer, response) = JsonSeqResponse::new(Response::builder()).unwrap();
+        tokio::spawn(async move {
+            writer.send(&json!({ "step": 1 })).await.unwrap();
+            writer.send("two").await.unwrap();
+        });
+
+        let out = response.into_body().into_bytes().await.unwrap();
+        assert_eq!(&out[..], b"\x1e{\"step\":1}\n\x1e\"two\"\n");
+
+        let records: Vec<_> = parse_json_seq::<Value>(&out).map(Result::unwrap).collect();
+        assert_eq!(records, [json!({ "step": 1 }), json!("two")]);
+    }
+
+    #[tokio::test]
+    async fn it_streams_projected_ndjson() {
+        let values = vec![
+            json!({ "id": 1, "output": { "text": "hi", "tokens": 3 }, "raw": "..." }),
+            json!({ "id": 2, "output": "not an object" }),
+        ];
+        let response = ndjson_response(values, &["id", "output.text"]).unwrap();
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        assert_eq!(
+            collect(response.into_body()).await,
+            "{\"id\":1,\"output\":{\"text\":\"hi\"}}\n{\"id\":2}\n"
+        );
+    }
+
+    #[test]
+    fn it_skips_past_truncated_records() {
+        let records: Vec<_> = parse_json_seq::<Value>(b"\x1e{\"a\":\x1e2\n").collect();
+        assert!(records[0].is_err());
+        assert_eq!(records[1].as_ref().unwrap(), &json!(2));
+    }
+}
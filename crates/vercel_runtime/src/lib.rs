@@ -1,43 +1,238 @@
+pub mod assets;
+pub mod batch;
+pub mod body;
+pub mod cache;
+#[cfg(feature = "debug")]
+pub mod capture;
+pub mod circuit_breaker;
+pub mod conditional;
+pub mod config;
+pub mod cron;
+pub mod env;
+pub mod error;
+pub mod ext;
+pub mod extract;
+pub mod forwarded;
+#[cfg(feature = "grpc_web")]
+pub mod grpc_web;
+pub mod headers;
+pub mod health;
 pub mod http;
+#[cfg(feature = "reqwest")]
+pub mod http_client;
+pub mod into_response;
+pub mod invocation;
+pub mod json;
+pub mod json_stream;
+pub mod lifecycle;
+pub mod logging;
+pub mod merge_patch;
+pub mod mock;
+pub mod multipart;
+pub mod openai;
+pub mod pagination;
+pub mod parsed_body;
+pub mod protection;
+pub mod query;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
+pub mod results;
+mod routing;
+pub mod runtime;
+pub mod signing;
+pub mod sse;
+pub mod steps;
+pub mod streaming;
+pub mod timing;
+pub mod trace;
+mod utils;
+pub mod vercel_id;
+pub mod webhook;
 
+use body::BodyStream;
+use futures_util::future::{ready, Either};
+use invocation::Invocation;
+use lambda_runtime::{Diagnostic, FunctionResponse, LambdaEvent, MetadataPrelude, StreamResponse};
 use request::Event;
-use request::VercelRequest;
+use request::{HeaderLimits, Rejection};
 use response::EventResponse;
+use serde::{de::DeserializeOwned, Serialize};
 use std::future::Future;
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::time::Instant;
+use trace::TraceContext;
+use tracing::{debug, error, warn, Instrument};
 
+pub use body::Body;
+pub use config::{HttpsPolicy, RuntimeConfig};
+pub use ext::{RequestExt, ResponseBuilderExt, ResponseExt};
+pub use into_response::IntoResponse;
+#[doc(hidden)]
+pub use routing::MatchedRoute;
+pub use routing::PathSegments;
+pub use tokio_util::sync::CancellationToken;
 pub use vercel_runtime_macro::bundled_api;
-pub use vercel_runtime_router::{Route, Router};
+pub use vercel_runtime_router::{Route, RouteMatch, Router, TrailingSlash};
 
 pub use lambda_http::{
-    http::StatusCode, service_fn, tower::ServiceBuilder, Body, Error, Request, RequestPayloadExt,
+    http::StatusCode, service_fn, tower::ServiceBuilder, Error, Request, RequestPayloadExt,
     Response,
 };
 pub use lambda_runtime::run as run_service;
 
-pub async fn run<T: FnMut(Request) -> F, F: Future<Output = Result<Response<Body>, Error>>>(
-    f: T,
-) -> Result<(), Error> {
+/// Runs `f` for every request.
+///
+/// The handler may return a full [`Response`] or anything else that implements
+/// [`IntoResponse`]. An `Err` fails the invocation.
+pub async fn run<T, F, R>(f: T) -> Result<(), Error>
+where
+    T: FnMut(Request) -> F,
+    F: Future<Output = Result<R, Error>>,
+    R: IntoResponse,
+{
+    run_with_config(RuntimeConfig::default(), f).await
+}
+
+pub async fn run_with_config<T, F, R>(config: RuntimeConfig, mut f: T) -> Result<(), Error>
+where
+    T: FnMut(Request) -> F,
+    F: Future<Output = Result<R, Error>>,
+    R: IntoResponse,
+{
+    if config.streaming {
+        runtime::set_streaming(true);
+    }
+    let config = Arc::new(config);
+    let request_config = config.clone();
+    let handler_config = config.clone();
     let handler = ServiceBuilder::new()
-        .map_request(process_request)
-        .map_response(process_response)
-        .service(service_fn(f));
+        .map_request(move |event| {
+            let mut request = decode_request(event, request_config.max_header_bytes);
+            request_config.override_method(&mut request);
+            request_config.attach_hooks(&mut request);
+            request_config.inspect_request(&request);
+            request
+        })
+        .and_then(move |(response, started)| {
+            into_function_response(config.clone(), response, started)
+        })
+        .service(service_fn(move |request: Request| {
+            let started = request.received_at();
+            let span = request
+                .extensions()
+                .get::<TraceContext>()
+                .map(TraceContext::span)
+                .unwrap_or_else(tracing::Span::none);
+            if let Some(response) = intercept(&handler_config, &request) {
+                return Either::Left(ready(response.map(|response| (response, started))));
+            }
+
+            let response = f(request);
+            Either::Right(
+                async move {
+                    response
+                        .await
+                        .map(|response| (response.into_response(), started))
+                }
+                .instrument(span),
+            )
+        }));
+
+    lambda_runtime::Runtime::new(handler)
+        .layer(lifecycle::LifecycleLayer)
+        .layer(lambda_runtime::layers::TracingLayer::new())
+        .run()
+        .await
+}
 
-    lambda_runtime::run(handler).await
+/// Runs a function invoked with a plain JSON event instead of an HTTP request.
+///
+/// The event is decoded into `I` and the returned `O` is sent back as JSON. A handler error is
+/// reported to the invoker as `{"errorType": "FunctionError", "errorMessage": "..."}`.
+pub async fn run_fn<I, O, T, F>(mut f: T) -> Result<(), Error>
+where
+    I: DeserializeOwned,
+    O: Serialize,
+    T: FnMut(I) -> F,
+    F: Future<Output = Result<O, Error>>,
+{
+    let handler = service_fn(move |event: LambdaEvent<I>| {
+        let response = f(event.payload);
+        async move {
+            response.await.map_err(|e| {
+                error!("Function failed: {}", e);
+                Diagnostic {
+                    error_type: "FunctionError".into(),
+                    error_message: e.to_string().into(),
+                }
+            })
+        }
+    });
+
+    lambda_runtime::Runtime::new(handler)
+        .layer(lifecycle::LifecycleLayer)
+        .layer(lambda_runtime::layers::TracingLayer::new())
+        .run()
+        .await
+}
+
+/// Answers requests that must not reach the handler.
+fn intercept(config: &RuntimeConfig, request: &Request) -> Option<Result<Response<Body>, Error>> {
+    if let Some(maintenance) = runtime::maintenance() {
+        if !maintenance.allows(request.uri().path()) {
+            return Some(maintenance.response());
+        }
+    }
+    if let Some(Rejection { status, reason }) = request.extensions().get::<Rejection>().copied() {
+        warn!(reason, "Rejecting request before it reaches the handler");
+        return Some(http::from_value(
+            status,
+            serde_json::json!({ "error": reason }),
+        ));
+    }
+    config.enforce_https(request)
 }
 
 pub fn process_request(event: Event) -> Request {
-    let (event, _context) = event.into_parts();
-    let parse_result = serde_json::from_str::<VercelRequest>(&event.body);
+    decode_request(event, None)
+}
+
+fn decode_request(event: Event, limits: Option<HeaderLimits>) -> Request {
+    let (event, context) = event.into_parts();
+    let parse_result = request::decode(&event.body, limits);
 
     match parse_result {
-        Ok(request) => {
+        Ok((request, oversized)) => {
             debug!("Deserialized Vercel proxy request successfully");
-            debug!("Request: {:?}", request);
-            let http_req: lambda_http::http::Request<Body> = request.into();
-            http_req.map(|b| b)
+            let mut http_req: lambda_http::http::Request<lambda_http::Body> = request.into();
+            http_req.extensions_mut().insert(Invocation::new(&context));
+            // Vercel de-chunks request bodies before invoking the function
+            let dechunked = http_req
+                .headers()
+                .contains_key(lambda_http::http::header::TRANSFER_ENCODING);
+            let framing = headers::normalize_body_framing(http_req.headers_mut()).and_then(|()| {
+                if dechunked {
+                    let length = http_req.body().len().into();
+                    http_req
+                        .headers_mut()
+                        .insert(lambda_http::http::header::CONTENT_LENGTH, length);
+                }
+                headers::check_content_length(http_req.headers(), http_req.body().len())
+            });
+            if let Err(reason) = framing {
+                http_req
+                    .extensions_mut()
+                    .insert(Rejection::bad_request(reason));
+            }
+            if let Some(rejection) = oversized {
+                http_req.extensions_mut().insert(rejection);
+            }
+            let trace = TraceContext::from_headers(http_req.headers());
+            trace::set_current(trace.clone());
+            timing::reset();
+            http_req.extensions_mut().insert(trace);
+            http_req
         }
         Err(e) => {
             error!("Could not deserialize event body to VercelRequest {:?}", e);
@@ -46,6 +241,65 @@ pub fn process_request(event: Event) -> Request {
     }
 }
 
-pub fn process_response(response: Response<Body>) -> EventResponse {
-    EventResponse::from(response)
+/// Converts a response into the buffered event format.
+///
+/// Fails for streaming bodies, buffer them first or use [`run`], which does that when streaming
+/// is not enabled.
+pub fn process_response(response: Response<Body>) -> Result<EventResponse, Error> {
+    EventResponse::try_from(response)
+}
+
+async fn into_function_response(
+    config: Arc<RuntimeConfig>,
+    response: Response<Body>,
+    started: Instant,
+) -> Result<FunctionResponse<EventResponse, BodyStream>, Error> {
+    let response = config.limit_response(response)?;
+    let (mut parts, body) = response.into_parts();
+    if let Some(pending) = parts.extensions.remove::<streaming::StreamHeaders>() {
+        pending.apply(&mut parts.headers);
+    }
+    if let Some(tasks) = parts.extensions.remove::<lifecycle::BackgroundTasks>() {
+        tasks.schedule(config.background_concurrency);
+    }
+    timing::apply(&mut parts.headers);
+    // For streaming bodies this is the time until the headers are ready
+    config.log_completion(parts.status, started.elapsed());
+    let body = response::enforce_empty_body(&mut parts, body);
+    let body = config.inspect_response(body);
+    config.apply_content_type(&mut parts.headers, &body);
+    // Headers have to be final before the first chunk of a streaming body goes out
+    config.apply_default_headers(&mut parts.headers);
+    headers::finalize_vary(&mut parts.headers, &mut parts.extensions);
+
+    if !runtime::supports_streaming() {
+        let body = match body {
+            Body::Stream(_) => {
+                warn!("Streaming is not enabled for this function, buffering the response body");
+                Body::Binary(body.into_bytes().await?.into())
+            }
+            body => body,
+        };
+        lifecycle::mark_responded();
+        return Ok(FunctionResponse::BufferedResponse(process_response(
+            Response::from_parts(parts, body),
+        )?));
+    }
+
+    // A function deployed for streaming sends every response in the streaming format
+    let stream = match body {
+        Body::Stream(stream) => stream,
+        body => BodyStream::new(futures_util::stream::once(async move {
+            body.into_bytes().await
+        })),
+    };
+    lifecycle::mark_responded();
+    Ok(FunctionResponse::StreamingResponse(StreamResponse {
+        metadata_prelude: MetadataPrelude {
+            status_code: parts.status,
+            headers: parts.headers,
+            cookies: Vec::new(),
+        },
+        stream,
+    }))
 }
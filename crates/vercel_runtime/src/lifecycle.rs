@@ -0,0 +1,355 @@
+//! Work that runs after the response has been sent, before the next invocation is fetched.
+//!
+//! Once the runtime delivered the response, tasks registered with [`wait_until_after_response`]
+//! run in registration order, then the finalizers registered with [`on_finish`] run last
+//! registered first. When the handler fails or panics the former are dropped without running,
+//! the latter run regardless.
+//!
+//! Work attached to a response with [`crate::ResponseExt::and_then_background`] is queued as a
+//! single task named `background`, whose futures run concurrently, up to
+//! [`crate::RuntimeConfig::background_concurrency`] at a time.
+//!
+//! Give tasks a name with [`wait_until_named`] to see them in [`pending`] and in the logs when
+//! they are dropped or panic.
+use futures_util::{
+    future::{BoxFuture, Then},
+    FutureExt, StreamExt,
+};
+use lambda_runtime::{
+    tower::{Layer, Service},
+    Error, LambdaInvocation,
+};
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tracing::{error, warn};
+
+// Functions process one invocation at a time, so a single queue covers the current invocation
+static FINALIZERS: Mutex<Vec<BoxFuture<'static, ()>>> = Mutex::new(Vec::new());
+static AFTER_RESPONSE: Mutex<Vec<AfterResponse>> = Mutex::new(Vec::new());
+static RUNNING: Mutex<Option<(Option<String>, Instant)>> = Mutex::new(None);
+static RESPONDED: AtomicBool = AtomicBool::new(false);
+
+struct AfterResponse {
+    name: Option<String>,
+    registered: Instant,
+    task: BoxFuture<'static, ()>,
+}
+
+/// A task registered with [`wait_until_after_response`], see [`pending`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskInfo {
+    /// Given with [`wait_until_named`].
+    pub name: Option<String>,
+    /// Time since the task was registered.
+    pub age: Duration,
+    pub state: TaskState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// Waiting for the response to be delivered or for the tasks before it.
+    Queued,
+    Running,
+}
+
+/// Registers a finalizer for the current invocation, see [`crate::RequestExt::on_finish`].
+pub fn on_finish(finalizer: impl Future<Output = ()> + Send + 'static) {
+    if let Ok(mut finalizers) = FINALIZERS.lock() {
+        finalizers.push(Box::pin(finalizer));
+    }
+}
+
+/// Registers work that only makes sense once the response went out, e.g. storing results the
+/// client was sent. It is dropped when the handler fails before responding.
+pub fn wait_until_after_response(task: impl Future<Output = ()> + Send + 'static) {
+    register(None, Box::pin(task));
+}
+
+/// Like [`wait_until_after_response`], naming the task for [`pending`] and the logs.
+pub fn wait_until_named(name: impl Into<String>, task: impl Future<Output = ()> + Send + 'static) {
+    register(Some(name.into()), Box::pin(task));
+}
+
+fn register(name: Option<String>, task: BoxFuture<'static, ()>) {
+    if let Ok(mut tasks) = AFTER_RESPONSE.lock() {
+        tasks.push(AfterResponse {
+            name,
+            registered: Instant::now(),
+            task,
+        });
+    }
+}
+
+/// Tasks of the current invocation that did not complete yet, the running one first.
+pub fn pending() -> Vec<TaskInfo> {
+    let running = RUNNING.lock().ok().and_then(|running| running.clone());
+    let running = running.map(|(name, registered)| TaskInfo {
+        name,
+        age: registered.elapsed(),
+        state: TaskState::Running,
+    });
+    let queued = AFTER_RESPONSE
+        .lock()
+        .map(|tasks| {
+            tasks
+                .iter()
+                .map(|task| TaskInfo {
+                    name: task.name.clone(),
+                    age: task.registered.elapsed(),
+                    state: TaskState::Queued,
+                })
+                .collect()
+        })
+        .unwrap_or_else(|_| Vec::new());
+    running.into_iter().chain(queued).collect()
+}
+
+fn label(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or("unnamed")
+}
+
+type Background = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// Work attached to a response, carried in its extensions until the runtime sends it.
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundTasks(Arc<Mutex<Vec<Background>>>);
+
+impl BackgroundTasks {
+    pub(crate) fn push<F, Fut>(&self, task: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if let Ok(mut tasks) = self.0.lock() {
+            tasks.push(Box::new(move || Box::pin(task())));
+        }
+    }
+
+    /// Queues the tasks to run once the response was delivered, see the module docs.
+    pub(crate) fn schedule(self, concurrency: Option<usize>) {
+        wait_until_named("background", self.run(concurrency));
+    }
+
+    async fn run(self, concurrency: Option<usize>) {
+        let tasks = self
+            .0
+            .lock()
+            .map(|mut tasks| std::mem::take(&mut *tasks))
+            .unwrap_or_default();
+        let limit = concurrency.unwrap_or(tasks.len()).max(1);
+        // Mapped up front, mapping inside the stream trips up the `Send` check of the queue
+        let tasks: Vec<_> = tasks.into_iter().map(run_background).collect();
+        futures_util::stream::iter(tasks)
+            .buffer_unordered(limit)
+            .collect::<()>()
+            .await;
+    }
+}
+
+fn run_background(task: Background) -> BoxFuture<'static, ()> {
+    Box::pin(async move {
+        if AssertUnwindSafe(task()).catch_unwind().await.is_err() {
+            error!("A background task attached to the response panicked");
+        }
+    })
+}
+
+impl std::fmt::Debug for BackgroundTasks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let len = self.0.lock().map_or(0, |tasks| tasks.len());
+        f.debug_tuple("BackgroundTasks").field(&len).finish()
+    }
+}
+
+/// Records that the handler produced a response for the current invocation.
+pub(crate) fn mark_responded() {
+    RESPONDED.store(true, Ordering::SeqCst);
+}
+
+/// Runs or drops the work registered for the current invocation, see the module docs.
+pub(crate) async fn finish_invocation(delivered: bool) {
+    let tasks = AFTER_RESPONSE
+        .lock()
+        .map(|mut tasks| std::mem::take(&mut *tasks))
+        .unwrap_or_default();
+    if RESPONDED.swap(false, Ordering::SeqCst) && delivered {
+        for AfterResponse {
+            name,
+            registered,
+            task,
+        } in tasks
+        {
+            set_running(Some((name.clone(), registered)));
+            if AssertUnwindSafe(task).catch_unwind().await.is_err() {
+                error!(
+                    task = label(&name),
+                    "A task waiting for the response panicked"
+                );
+            }
+        }
+        set_running(None);
+    } else if !tasks.is_empty() {
+        let names: Vec<_> = tasks.iter().map(|task| label(&task.name)).collect();
+        warn!(
+            tasks = ?names,
+            "Dropping tasks waiting for a response that was not delivered"
+        );
+    }
+    run_finalizers().await;
+}
+
+fn set_running(task: Option<(Option<String>, Instant)>) {
+    if let Ok(mut running) = RUNNING.lock() {
+        *running = task;
+    }
+}
+
+/// Awaits the registered finalizers, last registered first, like `Drop`.
+///
+/// A panicking finalizer is logged and does not prevent the remaining ones from running.
+pub(crate) async fn run_finalizers() {
+    loop {
+        let Some(finalizer) = FINALIZERS.lock().ok().and_then(|mut f| f.pop()) else {
+            return;
+        };
+        if AssertUnwindSafe(finalizer).catch_unwind().await.is_err() {
+            error!("A finalizer panicked");
+        }
+    }
+}
+
+/// Runs finalizers once the invocation was answered.
+pub(crate) struct LifecycleLayer;
+
+impl<S> Layer<S> for LifecycleLayer {
+    type Service = LifecycleService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LifecycleService { inner }
+    }
+}
+
+pub(crate) struct LifecycleService<S> {
+    inner: S,
+}
+
+type Finish = BoxFuture<'static, Result<(), Error>>;
+
+impl<S> Service<LambdaInvocation> for LifecycleService<S>
+where
+    S: Service<LambdaInvocation, Response = (), Error = Error>,
+{
+    type Response = ();
+    type Error = Error;
+    type Future = Then<S::Future, Finish, fn(Result<(), Error>) -> Finish>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, invocation: LambdaInvocation) -> Self::Future {
+        // The handler already answered, successfully or not, when the inner service resolves
+        self.inner.call(invocation).then(|result| {
+            Box::pin(async move {
+                finish_invocation(result.is_ok()).await;
+                crate::invocation::stop_deadline_watcher();
+                result
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        finish_invocation, mark_responded, on_finish, pending, run_finalizers,
+        wait_until_after_response, wait_until_named, BackgroundTasks, TaskState,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::Barrier;
+
+    #[tokio::test]
+    async fn it_runs_background_tasks_concurrently() {
+        let tasks = BackgroundTasks::default();
+        let barrier = Arc::new(Barrier::new(2));
+        let done = Arc::new(Mutex::new(0));
+        for _ in 0..2 {
+            let (barrier, done) = (barrier.clone(), done.clone());
+            // Each task waits for the other one, so running them in sequence never finishes
+            tasks.push(move || async move {
+                barrier.wait().await;
+                *done.lock().unwrap() += 1;
+            });
+        }
+        tasks.push(|| async { panic!("store failed") });
+
+        tokio::time::timeout(Duration::from_secs(1), tasks.run(Some(2)))
+            .await
+            .unwrap();
+        assert_eq!(*done.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_runs_finalizers_in_reverse_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let calls = calls.clone();
+            on_finish(async move {
+                if i == 1 {
+                    panic!("cleanup failed");
+                }
+                calls.lock().unwrap().push(i);
+            });
+        }
+
+        run_finalizers().await;
+        assert_eq!(*calls.lock().unwrap(), [2, 0]);
+
+        // Kept in one test since the queues are process wide
+        calls.lock().unwrap().clear();
+        for (i, after_response) in [(0, true), (1, false), (2, true)] {
+            let calls = calls.clone();
+            let task = async move { calls.lock().unwrap().push(i) };
+            match after_response {
+                true => wait_until_after_response(task),
+                false => on_finish(task),
+            }
+        }
+        finish_invocation(true).await;
+        // Not marked as responded, e.g. the handler failed
+        assert_eq!(*calls.lock().unwrap(), [1]);
+
+        calls.lock().unwrap().clear();
+        for i in 0..2 {
+            let calls = calls.clone();
+            wait_until_after_response(async move { calls.lock().unwrap().push(i) });
+        }
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let inspect = seen.clone();
+        // Panics in tasks are only logged, so the assertions happen outside
+        wait_until_named(
+            "inspect",
+            async move { *inspect.lock().unwrap() = pending() },
+        );
+        let queued = pending();
+        assert_eq!(queued.len(), 3);
+        assert!(queued.iter().all(|task| task.state == TaskState::Queued));
+        mark_responded();
+        finish_invocation(true).await;
+        assert_eq!(*calls.lock().unwrap(), [0, 1]);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].name.as_deref(), Some("inspect"));
+        assert_eq!(seen[0].state, TaskState::Running);
+        assert!(pending().is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use lambda_http::http::StatusCode;
+use std::{fmt, sync::Arc, time::Duration};
+use tracing::{debug, error, info, trace, warn, Level};
+
+type LevelFn = Arc<dyn Fn(StatusCode) -> Level + Send + Sync>;
+
+/// `error` for 5xx, `warn` for 4xx and `info` for everything else.
+pub fn default_level(status: StatusCode) -> Level {
+    if status.is_server_error() {
+        Level::ERROR
+    } else if status.is_client_error() {
+        Level::WARN
+    } else {
+        Level::INFO
+    }
+}
+
+/// Emits one event per completed request, at a level picked from the response status.
+#[derive(Clone, Default)]
+pub(crate) struct CompletionLog {
+    level: Option<LevelFn>,
+}
+
+impl fmt::Debug for CompletionLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionLog")
+            .field("level", &self.level.is_some())
+            .finish()
+    }
+}
+
+impl CompletionLog {
+    pub fn with_level(level: impl Fn(StatusCode) -> Level + Send + Sync + 'static) -> Self {
+        CompletionLog {
+            level: Some(Arc::new(level)),
+        }
+    }
+
+    pub fn level(&self, status: StatusCode) -> Level {
+        match &self.level {
+            Some(level) => level(status),
+            None => default_level(status),
+        }
+    }
+
+    pub fn log(&self, status: StatusCode, latency: Duration) {
+        let level = self.level(status);
+        let status = status.as_u16();
+        let latency_ms = latency.as_millis() as u64;
+        // The level of tracing macros has to be a constant
+        match level {
+            Level::ERROR => error!(status, latency_ms, "request completed"),
+            Level::WARN => warn!(status, latency_ms, "request completed"),
+            Level::INFO => info!(status, latency_ms, "request completed"),
+            Level::DEBUG => debug!(status, latency_ms, "request completed"),
+            _ => trace!(status, latency_ms, "request completed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompletionLog;
+    use lambda_http::http::StatusCode;
+    use tracing::Level;
+
+    #[test]
+    fn it_maps_status_to_level() {
+        let log = CompletionLog::default();
+        assert_eq!(log.level(StatusCode::BAD_GATEWAY), Level::ERROR);
+        assert_eq!(log.level(StatusCode::NOT_FOUND), Level::WARN);
+        assert_eq!(log.level(StatusCode::FOUND), Level::INFO);
+
+        let log = CompletionLog::with_level(|status| match status {
+            StatusCode::NOT_FOUND => Level::DEBUG,
+            status => super::default_level(status),
+        });
+        assert_eq!(log.level(StatusCode::NOT_FOUND), Level::DEBUG);
+        assert_eq!(log.level(StatusCode::INTERNAL_SERVER_ERROR), Level::ERROR);
+    }
+}
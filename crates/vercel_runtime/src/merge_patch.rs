@@ -0,0 +1,101 @@
+//! JSON Merge Patch (RFC 7396) for partial updates.
+use lambda_http::{http::header, Error, Request};
+use serde_json::Value;
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Applies `patch` to `target` in place.
+///
+/// Objects are merged recursively, `null` members delete the key and everything else,
+/// including arrays, replaces the target value.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.remove(key);
+            }
+            value => merge_patch(target.entry(key.clone()).or_insert(Value::Null), value),
+        }
+    }
+}
+
+/// Parses a merge patch body, requiring the `application/merge-patch+json` content type.
+pub fn parse_merge_patch(req: &Request) -> Result<Value, Error> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(str::trim);
+    if !content_type.is_some_and(|c| c.eq_ignore_ascii_case(MERGE_PATCH_CONTENT_TYPE)) {
+        return Err(Error::from(format!(
+            "expected content type {}",
+            MERGE_PATCH_CONTENT_TYPE
+        )));
+    }
+    Ok(serde_json::from_slice(req.body())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_patch, parse_merge_patch};
+    use lambda_http::{http, Body};
+    use serde_json::json;
+
+    #[test]
+    fn it_applies_rfc_7396_examples() {
+        let mut target = json!({
+            "title": "Goodbye!",
+            "author": { "givenName": "John", "familyName": "Doe" },
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        });
+        merge_patch(
+            &mut target,
+            &json!({
+                "title": "Hello!",
+                "phoneNumber": "+01-123-456-7890",
+                "author": { "familyName": null },
+                "tags": ["example"]
+            }),
+        );
+        assert_eq!(
+            target,
+            json!({
+                "title": "Hello!",
+                "author": { "givenName": "John" },
+                "tags": ["example"],
+                "content": "This will be unchanged",
+                "phoneNumber": "+01-123-456-7890"
+            })
+        );
+
+        let mut target = json!(["a"]);
+        merge_patch(&mut target, &json!({ "a": { "bb": { "ccc": null } } }));
+        assert_eq!(target, json!({ "a": { "bb": {} } }));
+    }
+
+    #[test]
+    fn it_checks_the_content_type() {
+        let request = |content_type: &str| {
+            http::Request::builder()
+                .header("content-type", content_type)
+                .body(Body::from(r#"{"step":2}"#))
+                .unwrap()
+        };
+        let patch = parse_merge_patch(&request("application/merge-patch+json; charset=utf-8"));
+        assert_eq!(patch.unwrap(), json!({ "step": 2 }));
+        assert!(parse_merge_patch(&request("application/json")).is_err());
+    }
+}
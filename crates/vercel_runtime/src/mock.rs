@@ -0,0 +1,223 @@
+//! Requests for handler tests that go through the same decoding as real invocations.
+//!
+//! ```no_run
+//! # use vercel_runtime::{http, mock, Error};
+//! # async fn example() -> Result<(), Error> {
+//! let request = mock::request()
+//!     .post("/api/step-v4/2")
+//!     .json(&serde_json::json!({ "input": "hello" }))
+//!     .build();
+//! let response = mock::invoke(|_req| async { http::ok("done") }, request).await?;
+//! assert_eq!(response.status(), 200);
+//! # Ok(())
+//! # }
+//! ```
+use crate::{
+    intercept, process_request, request::VercelEvent, Body, Error, IntoResponse, Request,
+    RuntimeConfig,
+};
+use base64::Engine;
+use lambda_http::http::{header, Method};
+use lambda_http::Response;
+use lambda_runtime::{Context, LambdaEvent};
+use serde::Serialize;
+use serde_json::json;
+use std::future::Future;
+
+/// Starts a `GET /` request from `127.0.0.1` to `localhost`.
+pub fn request() -> MockRequest {
+    MockRequest {
+        method: Method::GET,
+        host: "localhost".to_owned(),
+        path: "/".to_owned(),
+        headers: Vec::new(),
+        body: None,
+    }
+}
+
+/// Builder for a request, see [`request`].
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    method: Method,
+    host: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+impl MockRequest {
+    pub fn get(self, path: &str) -> Self {
+        self.method(Method::GET, path)
+    }
+
+    pub fn post(self, path: &str) -> Self {
+        self.method(Method::POST, path)
+    }
+
+    pub fn put(self, path: &str) -> Self {
+        self.method(Method::PUT, path)
+    }
+
+    pub fn patch(self, path: &str) -> Self {
+        self.method(Method::PATCH, path)
+    }
+
+    pub fn delete(self, path: &str) -> Self {
+        self.method(Method::DELETE, path)
+    }
+
+    /// Sets the method and the path, which may include a query string.
+    pub fn method(mut self, method: Method, path: &str) -> Self {
+        self.method = method;
+        self.path = path.to_owned();
+        self
+    }
+
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = host.to_owned();
+        self
+    }
+
+    /// Adds a header, headers with the same name are sent as one comma separated value like
+    /// Vercel does.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push((name.to_ascii_lowercase(), value.to_owned()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Serializes `value` as the body and sets `Content-Type: application/json`.
+    pub fn json<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("mock request body must serialize");
+        self.header(header::CONTENT_TYPE.as_str(), "application/json")
+            .body(body)
+    }
+
+    /// The invocation event Vercel would send for this request.
+    ///
+    /// The body is base64 encoded and the headers Vercel injects (`host`, `x-forwarded-*`,
+    /// `x-real-ip`, `x-vercel-id`) are added unless they were set explicitly.
+    pub fn event(&self) -> serde_json::Value {
+        let injected = [
+            ("host", self.host.as_str()),
+            ("x-forwarded-host", self.host.as_str()),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-for", "127.0.0.1"),
+            ("x-real-ip", "127.0.0.1"),
+            ("x-vercel-id", "dev1::mock"),
+        ];
+        let mut headers = serde_json::Map::new();
+        for (name, value) in injected {
+            headers.insert(name.to_owned(), json!(value));
+        }
+        let mut explicit = serde_json::Map::new();
+        for (name, value) in &self.headers {
+            let value = match explicit.remove(name) {
+                Some(serde_json::Value::String(previous)) => format!("{}, {}", previous, value),
+                _ => value.clone(),
+            };
+            explicit.insert(name.clone(), json!(value));
+        }
+        headers.extend(explicit);
+
+        let mut event = json!({
+            "host": self.host,
+            "path": self.path,
+            "method": self.method.as_str(),
+            "headers": headers,
+        });
+        if let Some(body) = &self.body {
+            event["body"] = json!(base64::prelude::BASE64_STANDARD.encode(body));
+            event["encoding"] = json!("base64");
+        }
+        json!({ "Action": "Invoke", "body": event.to_string() })
+    }
+
+    /// Decodes [`MockRequest::event`] the way the runtime decodes invocations.
+    pub fn build(&self) -> Request {
+        let event: VercelEvent =
+            serde_json::from_value(self.event()).expect("mock events are valid Vercel events");
+        process_request(LambdaEvent::new(event, Context::default()))
+    }
+}
+
+/// Calls `handler` with `request`, answering it like the runtime would when the request must
+/// not reach the handler, e.g. because of conflicting body framing.
+pub async fn invoke<T, F, R>(handler: T, request: Request) -> Result<Response<Body>, Error>
+where
+    T: FnOnce(Request) -> F,
+    F: Future<Output = Result<R, Error>>,
+    R: IntoResponse,
+{
+    if let Some(response) = intercept(&RuntimeConfig::default(), &request) {
+        return response;
+    }
+    handler(request).await.map(IntoResponse::into_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invoke, request};
+    use crate::{Body, RequestExt};
+    use lambda_http::http::StatusCode;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn it_decodes_mock_requests_like_invocations() {
+        let request = request()
+            .post("/api/step-v4/2?trace=1")
+            .header("X-Step", "a")
+            .header("x-step", "b")
+            .json(&json!({ "n": 2 }))
+            .build();
+        assert_eq!(request.uri(), "https://localhost/api/step-v4/2?trace=1");
+        assert_eq!(request.headers()["x-step"], "a, b");
+        assert_eq!(request.headers()["x-forwarded-proto"], "https");
+        assert!(request.invocation().is_some());
+
+        let response = invoke(
+            |req| async move {
+                let body: serde_json::Value = serde_json::from_slice(req.body())?;
+                Ok((StatusCode::CREATED, body))
+            },
+            request,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        assert_eq!(body, r#"{"n":2}"#);
+    }
+
+    #[test]
+    fn it_replaces_chunked_framing_with_the_body_length() {
+        let request = request()
+            .post("/api/upload")
+            .header("transfer-encoding", "chunked")
+            .body("complete body")
+            .build();
+        assert!(!request.headers().contains_key("transfer-encoding"));
+        assert_eq!(request.headers()["content-length"], "13");
+        assert_eq!(request.body().as_ref(), b"complete body");
+    }
+
+    #[tokio::test]
+    async fn it_rejects_truncated_bodies() {
+        let request = request()
+            .post("/api/upload")
+            .header("content-length", "20")
+            .body("cut off")
+            .build();
+        let response = invoke(|_req| async { Ok("unreachable") }, request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
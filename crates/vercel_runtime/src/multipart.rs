@@ -0,0 +1,129 @@
+//! Parsing of buffered `multipart/form-data` bodies (RFC 7578).
+use lambda_http::Error;
+
+/// One field or file of a multipart body.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Part {
+    /// The `name` of the form field.
+    pub name: Option<String>,
+    /// Set for file uploads.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    /// The data as text, `None` when it is not valid UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+}
+
+/// The `boundary` parameter of a `multipart/*` content type.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Splits `body` at `boundary` into its parts, ignoring the preamble and epilogue.
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Result<Vec<Part>, Error> {
+    let delimiter = format!("--{}", boundary);
+    let mut rest = match find(body, delimiter.as_bytes()) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return Err(Error::from("multipart boundary not found")),
+    };
+
+    let close = format!("\r\n{}", delimiter);
+    let mut parts = Vec::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| Error::from("malformed multipart delimiter"))?;
+        let end = find(rest, close.as_bytes())
+            .ok_or_else(|| Error::from("unterminated multipart body"))?;
+        parts.push(parse_part(&rest[..end])?);
+        rest = &rest[end + close.len()..];
+    }
+}
+
+fn parse_part(part: &[u8]) -> Result<Part, Error> {
+    let (head, data) = match part.strip_prefix(b"\r\n") {
+        // A part without headers
+        Some(data) => (&[][..], data),
+        None => {
+            let end = find(part, b"\r\n\r\n")
+                .ok_or_else(|| Error::from("malformed multipart part headers"))?;
+            (&part[..end], &part[end + 4..])
+        }
+    };
+
+    let mut parsed = Part {
+        data: data.to_vec(),
+        ..Default::default()
+    };
+    for line in std::str::from_utf8(head)?.split("\r\n") {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-type") {
+            parsed.content_type = Some(value.to_owned());
+        } else if name.eq_ignore_ascii_case("content-disposition") {
+            for param in value.split(';').skip(1) {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"').to_owned();
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "name" => parsed.name = Some(value),
+                    "filename" => parsed.filename = Some(value),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundary, parse_multipart};
+
+    #[test]
+    fn it_parses_fields_and_files() {
+        let content_type = "multipart/form-data; boundary=\"XyZ\"";
+        assert_eq!(boundary(content_type), Some("XyZ"));
+
+        let body = "preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            Hello\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            line 1\r\nline 2\r\n\
+            --XyZ--\r\n";
+        let parts = parse_multipart(body.as_bytes(), "XyZ").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name.as_deref(), Some("title"));
+        assert_eq!(parts[0].text(), Some("Hello"));
+        assert_eq!(parts[1].filename.as_deref(), Some("a.txt"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("text/plain"));
+        assert_eq!(parts[1].text(), Some("line 1\r\nline 2"));
+
+        assert!(parse_multipart(b"--XyZ\r\n\r\nunterminated", "XyZ").is_err());
+        assert!(parse_multipart(b"no boundary", "XyZ").is_err());
+    }
+}
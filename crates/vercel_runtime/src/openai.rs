@@ -0,0 +1,243 @@
+//! OpenAI compatible response objects.
+//!
+//! ```
+//! # use vercel_runtime::openai::CompletionBuilder;
+//! let completion = CompletionBuilder::new("stream-reconstructed")
+//!     .content("Hello!")
+//!     .build();
+//! assert_eq!(completion.object, "chat.completion");
+//! ```
+use crate::runtime;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// A `chat.completion` object with a single choice.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatCompletion {
+    pub id: String,
+    pub object: String,
+    /// Unix timestamp in seconds.
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<Choice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// Token accounting of a completion.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Choice {
+    pub index: u32,
+    pub message: Message,
+    pub finish_reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+/// Builds a [`ChatCompletion`] with a generated `chatcmpl-` id and the current time.
+///
+/// Both follow [`runtime::freeze_time`] in tests.
+#[derive(Debug, Clone)]
+pub struct CompletionBuilder {
+    model: String,
+    content: String,
+    finish_reason: String,
+    id: Option<String>,
+    created: Option<SystemTime>,
+    usage: Option<Usage>,
+}
+
+impl CompletionBuilder {
+    pub fn new(model: impl Into<String>) -> Self {
+        CompletionBuilder {
+            model: model.into(),
+            content: String::new(),
+            finish_reason: "stop".to_owned(),
+            id: None,
+            created: None,
+            usage: None,
+        }
+    }
+
+    /// Reassembles a streamed completion into the object the same request returns without
+    /// streaming.
+    ///
+    /// Takes the `chat.completion.chunk` objects in order: `id`, `model` and `created` come
+    /// from the first chunk, the content is the concatenation of the `delta.content` values,
+    /// and `finish_reason` and `usage` are taken from the last chunk that carries them. Any of
+    /// them can still be overridden on the returned builder.
+    pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a serde_json::Value>) -> Self {
+        let mut builder = CompletionBuilder::new("");
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            if i == 0 {
+                let str_field = |name| chunk.get(name).and_then(|v| v.as_str()).map(str::to_owned);
+                builder.model = str_field("model").unwrap_or_default();
+                builder.id = str_field("id");
+                builder.created = chunk
+                    .get("created")
+                    .and_then(|v| v.as_u64())
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+            }
+            let choice = chunk.pointer("/choices/0");
+            if let Some(content) = choice
+                .and_then(|c| c.pointer("/delta/content"))
+                .and_then(|v| v.as_str())
+            {
+                builder.content.push_str(content);
+            }
+            if let Some(reason) = choice
+                .and_then(|c| c.get("finish_reason"))
+                .and_then(|v| v.as_str())
+            {
+                builder.finish_reason = reason.to_owned();
+            }
+            if let Some(usage) = chunk
+                .get("usage")
+                .and_then(|usage| serde_json::from_value(usage.clone()).ok())
+            {
+                builder.usage = Some(usage);
+            }
+        }
+        builder
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// The assistant message of the choice.
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    /// `stop` by default.
+    pub fn finish_reason(mut self, finish_reason: impl Into<String>) -> Self {
+        self.finish_reason = finish_reason.into();
+        self
+    }
+
+    /// Replaces the generated id, e.g. for deterministic tests.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Replaces the current time as `created`, e.g. for deterministic tests.
+    pub fn created(mut self, created: SystemTime) -> Self {
+        self.created = Some(created);
+        self
+    }
+
+    pub fn usage(mut self, prompt_tokens: u64, completion_tokens: u64) -> Self {
+        self.usage = Some(Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        });
+        self
+    }
+
+    pub fn build(self) -> ChatCompletion {
+        let created = self
+            .created
+            .unwrap_or_else(runtime::now)
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        ChatCompletion {
+            id: self
+                .id
+                .unwrap_or_else(|| format!("chatcmpl-{:016x}", runtime::unique_id())),
+            object: "chat.completion".to_owned(),
+            created,
+            model: self.model,
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: "assistant".to_owned(),
+                    content: self.content,
+                },
+                finish_reason: self.finish_reason,
+            }],
+            usage: self.usage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompletionBuilder;
+    use serde_json::json;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn it_reconstructs_streamed_completions() {
+        let chunks = [
+            json!({"id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1_700_000_000,
+                   "model": "gpt-4o", "choices": [{"index": 0, "delta": {"role": "assistant"},
+                   "finish_reason": null}]}),
+            json!({"id": "chatcmpl-1", "choices": [{"index": 0, "delta": {"content": "Hel"}}]}),
+            json!({"id": "chatcmpl-1", "choices": [{"index": 0, "delta": {"content": "lo"},
+                   "finish_reason": "stop"}]}),
+            json!({"id": "chatcmpl-1", "choices": [],
+                   "usage": {"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5}}),
+        ];
+        let streamed = CompletionBuilder::from_chunks(&chunks).build();
+        let direct = CompletionBuilder::new("gpt-4o")
+            .content("Hello")
+            .id("chatcmpl-1")
+            .created(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .usage(3, 2)
+            .build();
+        assert_eq!(streamed, direct);
+    }
+
+    #[test]
+    fn it_builds_canonical_completions() {
+        let completion = CompletionBuilder::new("gpt-4o")
+            .content("Hi")
+            .finish_reason("length")
+            .id("chatcmpl-test")
+            .created(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .build();
+        assert_eq!(
+            serde_json::to_value(&completion).unwrap(),
+            json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "created": 1_700_000_000,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "Hi" },
+                    "finish_reason": "length"
+                }]
+            })
+        );
+
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = crate::runtime::freeze_time(at);
+        let frozen = CompletionBuilder::new("m").build();
+        assert_eq!(frozen.id, "chatcmpl-0000000000000001");
+        assert_eq!(frozen.created, 1_700_000_000);
+        drop(clock);
+
+        let a = CompletionBuilder::new("m").build();
+        let b = CompletionBuilder::new("m").build();
+        assert!(a.id.starts_with("chatcmpl-"));
+        assert_ne!(a.id, b.id);
+    }
+}
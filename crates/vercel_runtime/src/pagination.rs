@@ -0,0 +1,90 @@
+//! A common envelope for paginated list endpoints.
+//!
+//! Requests pass `?cursor=<opaque>&limit=<n>`, responses are a [`Page`]:
+//! `{"items": [...], "next_cursor": "...", "total": 42}`. The last page has no `next_cursor`.
+use crate::Body;
+use lambda_http::{http::StatusCode, Error, Response};
+use serde::Serialize;
+
+/// Items per page when the request does not ask for a limit.
+pub const DEFAULT_LIMIT: usize = 20;
+/// Upper bound for the requested limit, so a single request cannot load a whole table.
+pub const MAX_LIMIT: usize = 100;
+
+/// One page of a list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page, `None` on the last one.
+    pub next_cursor: Option<String>,
+    /// Size of the whole list, when it is cheap to count.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<String>) -> Self {
+        Page {
+            items,
+            next_cursor,
+            total: None,
+        }
+    }
+
+    pub fn total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+}
+
+/// A JSON response with the page as body.
+pub fn page<T: Serialize>(status: StatusCode, page: &Page<T>) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::Text(serde_json::to_string(page)?))?)
+}
+
+/// The non-empty `cursor` query parameter.
+pub fn cursor(query: &str) -> Option<String> {
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "cursor")
+        .map(|(_, value)| value.into_owned())
+        .filter(|cursor| !cursor.is_empty())
+}
+
+/// The `limit` query parameter clamped to `1..=max`, `default` when it is absent or invalid.
+pub fn limit(query: &str, default: usize, max: usize) -> usize {
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "limit")
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(default)
+        .clamp(1, max.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cursor, limit, page, Page, DEFAULT_LIMIT, MAX_LIMIT};
+    use crate::Body;
+    use lambda_http::http::StatusCode;
+
+    #[test]
+    fn it_reads_pagination_parameters() {
+        assert_eq!(cursor("cursor=abc%3D&limit=5").as_deref(), Some("abc="));
+        assert_eq!(cursor("cursor="), None);
+        assert_eq!(limit("limit=5", DEFAULT_LIMIT, MAX_LIMIT), 5);
+        assert_eq!(limit("limit=100000", DEFAULT_LIMIT, MAX_LIMIT), MAX_LIMIT);
+        assert_eq!(limit("limit=0", DEFAULT_LIMIT, MAX_LIMIT), 1);
+        assert_eq!(limit("limit=-1", DEFAULT_LIMIT, MAX_LIMIT), DEFAULT_LIMIT);
+        assert_eq!(limit("", DEFAULT_LIMIT, MAX_LIMIT), DEFAULT_LIMIT);
+    }
+
+    #[test]
+    fn it_serializes_pages() {
+        let response = page(StatusCode::OK, &Page::new(vec![1, 2], None).total(2)).unwrap();
+        let Body::Text(body) = response.body() else {
+            panic!("expected a text body");
+        };
+        assert_eq!(body, r#"{"items":[1,2],"next_cursor":null,"total":2}"#);
+    }
+}
@@ -0,0 +1,103 @@
+//! Content type driven parsing of request bodies, see [`crate::RequestExt::body_any`].
+use crate::multipart::{self, Part};
+use lambda_http::{http::header, Error, Request};
+
+/// A request body parsed according to its `Content-Type`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedBody {
+    /// `application/json` and `application/*+json`.
+    Json(serde_json::Value),
+    /// `application/x-www-form-urlencoded`, pairs in their original order.
+    Form(Vec<(String, String)>),
+    /// `multipart/form-data`.
+    Multipart(Vec<Part>),
+    /// Any other or a missing content type.
+    Raw(Vec<u8>),
+}
+
+/// Parses the body of `req`.
+///
+/// Fails only when the body does not match the content type it declares.
+pub fn parse_body(req: &Request) -> Result<ParsedBody, Error> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let body: &[u8] = req.body();
+
+    Ok(match essence.as_str() {
+        "application/json" => ParsedBody::Json(serde_json::from_slice(body)?),
+        e if e.starts_with("application/") && e.ends_with("+json") => {
+            ParsedBody::Json(serde_json::from_slice(body)?)
+        }
+        "application/x-www-form-urlencoded" => ParsedBody::Form(
+            form_urlencoded::parse(body)
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect(),
+        ),
+        "multipart/form-data" => {
+            let boundary = multipart::boundary(content_type)
+                .ok_or_else(|| Error::from("multipart body without a boundary"))?;
+            ParsedBody::Multipart(multipart::parse_multipart(body, boundary)?)
+        }
+        _ => ParsedBody::Raw(body.to_vec()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_body, ParsedBody};
+    use lambda_http::{http, Body, Request};
+    use serde_json::json;
+
+    fn request(content_type: Option<&str>, body: &str) -> Request {
+        let mut builder = http::Request::builder();
+        if let Some(content_type) = content_type {
+            builder = builder.header("content-type", content_type);
+        }
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    #[test]
+    fn it_dispatches_on_content_type() {
+        let body = parse_body(&request(
+            Some("application/json; charset=utf-8"),
+            r#"{"a":1}"#,
+        ));
+        assert_eq!(body.unwrap(), ParsedBody::Json(json!({ "a": 1 })));
+        let body = parse_body(&request(Some("application/vnd.api+json"), "[]"));
+        assert_eq!(body.unwrap(), ParsedBody::Json(json!([])));
+
+        let body = parse_body(&request(
+            Some("application/x-www-form-urlencoded"),
+            "a=1&b=x+y",
+        ));
+        assert_eq!(
+            body.unwrap(),
+            ParsedBody::Form(vec![("a".into(), "1".into()), ("b".into(), "x y".into())])
+        );
+
+        let multipart = "--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b--";
+        let body = parse_body(&request(Some("multipart/form-data; boundary=b"), multipart));
+        let ParsedBody::Multipart(parts) = body.unwrap() else {
+            panic!("expected multipart");
+        };
+        assert_eq!(parts[0].text(), Some("1"));
+
+        let body = parse_body(&request(Some("text/csv"), "a,b"));
+        assert_eq!(body.unwrap(), ParsedBody::Raw(b"a,b".to_vec()));
+        assert_eq!(
+            parse_body(&request(None, "")).unwrap(),
+            ParsedBody::Raw(Vec::new())
+        );
+        assert!(parse_body(&request(Some("application/json"), "{")).is_err());
+        assert!(parse_body(&request(Some("multipart/form-data"), "")).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::utils::constant_time_eq;
+use lambda_http::http::HeaderMap;
+
+/// Header carrying the Protection Bypass for Automation secret.
+pub const PROTECTION_BYPASS_HEADER: &str = "x-vercel-protection-bypass";
+
+/// Outcome of checking a request against the deployment protection bypass secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionBypass {
+    /// The request did not carry a bypass token.
+    Missing,
+    /// A token was sent, but it does not match.
+    Invalid,
+    Valid,
+}
+
+impl ProtectionBypass {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, ProtectionBypass::Valid)
+    }
+}
+
+pub fn has_bypass_header(headers: &HeaderMap) -> bool {
+    headers.contains_key(PROTECTION_BYPASS_HEADER)
+}
+
+/// Compares the bypass token with `expected`.
+///
+/// An empty `expected`, e.g. an unset env var, never validates, so a missing secret does not
+/// open the deployment to anyone sending an empty header.
+pub fn check_bypass(headers: &HeaderMap, expected: &str) -> ProtectionBypass {
+    match headers.get(PROTECTION_BYPASS_HEADER) {
+        None => ProtectionBypass::Missing,
+        Some(_) if expected.is_empty() => ProtectionBypass::Invalid,
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            ProtectionBypass::Valid
+        }
+        Some(_) => ProtectionBypass::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_bypass, ProtectionBypass, PROTECTION_BYPASS_HEADER};
+    use lambda_http::http::HeaderMap;
+
+    #[test]
+    fn it_checks_bypass_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(check_bypass(&headers, "secret"), ProtectionBypass::Missing);
+
+        headers.insert(PROTECTION_BYPASS_HEADER, "secreT".parse().unwrap());
+        assert_eq!(check_bypass(&headers, "secret"), ProtectionBypass::Invalid);
+
+        headers.insert(PROTECTION_BYPASS_HEADER, "secret".parse().unwrap());
+        assert_eq!(check_bypass(&headers, "secret"), ProtectionBypass::Valid);
+        assert_eq!(check_bypass(&headers, ""), ProtectionBypass::Invalid);
+
+        headers.insert(PROTECTION_BYPASS_HEADER, "".parse().unwrap());
+        assert_eq!(check_bypass(&headers, ""), ProtectionBypass::Invalid);
+    }
+}
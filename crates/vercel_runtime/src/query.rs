@@ -0,0 +1,342 @@
+//! Typed query string parsing.
+//!
+//! Both conventions HTML forms and client libraries use for lists are understood, so a
+//! `tags: Vec<String>` field is filled from either of
+//!
+//! - repeated keys: `?tag=a&tag=b`
+//! - bracket keys: `?tag[]=a&tag[]=b`
+//!
+//! A key given more than once for a single-value field takes its last value. List fields are
+//! missing, rather than empty, when the key is absent, so mark them `#[serde(default)]`.
+//!
+//! ```
+//! # use serde::Deserialize;
+//! #[derive(Deserialize)]
+//! struct Filter {
+//!     #[serde(default)]
+//!     tag: Vec<String>,
+//!     page: Option<u32>,
+//! }
+//!
+//! let filter: Filter = vercel_runtime::query::from_query("tag=a&tag[]=b&page=1&page=2").unwrap();
+//! assert_eq!(filter.tag, ["a", "b"]);
+//! assert_eq!(filter.page, Some(2));
+//! ```
+//!
+//! The other way around, [`QueryBuilder`] encodes parameters onto an outbound URL.
+use lambda_http::Error;
+use serde::de::{
+    self,
+    value::{Error as DeError, MapDeserializer, SeqDeserializer},
+    DeserializeOwned, IntoDeserializer, Visitor,
+};
+use std::fmt::Display;
+
+/// Deserializes a raw (still percent-encoded) query string into `T`.
+pub fn from_query<T: DeserializeOwned>(query: &str) -> Result<T, Error> {
+    let mut entries: Vec<(String, Values)> = Vec::new();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let key = key.strip_suffix("[]").unwrap_or(&key);
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, values)) => values.0.push(value.into_owned()),
+            None => entries.push((key.to_owned(), Values(vec![value.into_owned()]))),
+        }
+    }
+
+    let deserializer = MapDeserializer::<_, DeError>::new(entries.into_iter());
+    T::deserialize(deserializer).map_err(|e| Error::from(format!("invalid query string: {}", e)))
+}
+
+/// Appends encoded query parameters to a URL, e.g. for upstream calls or callback URLs.
+///
+/// Parameters already on the URL are kept byte for byte, so signed or pre-encoded URLs stay
+/// valid, as is a `#fragment`. Only the parameters added or replaced are encoded.
+///
+/// ```
+/// # use vercel_runtime::query::QueryBuilder;
+/// let url = QueryBuilder::new("https://api.example.com/search?lang=en")
+///     .append("q", "rust & lambda")
+///     .append("page", 2)
+///     .build();
+/// assert_eq!(url, "https://api.example.com/search?lang=en&q=rust+%26+lambda&page=2");
+/// ```
+///
+/// Combine it with [`crate::RequestExt::absolute_url`] to point a callback at this deployment.
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    base: String,
+    /// Encoded `key=value` pairs, as they appear in the URL.
+    pairs: Vec<String>,
+    fragment: Option<String>,
+}
+
+impl QueryBuilder {
+    pub fn new(url: &str) -> Self {
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment.to_owned())),
+            None => (url, None),
+        };
+        let (base, query) = url.split_once('?').unwrap_or((url, ""));
+        let pairs = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(str::to_owned)
+            .collect();
+        QueryBuilder {
+            base: base.to_owned(),
+            pairs,
+            fragment,
+        }
+    }
+
+    /// Adds `key=value`, even when `key` is already present.
+    pub fn append(mut self, key: &str, value: impl Display) -> Self {
+        let pair = form_urlencoded::Serializer::new(String::new())
+            .append_pair(key, &value.to_string())
+            .finish();
+        self.pairs.push(pair);
+        self
+    }
+
+    /// Adds `key=value` when there is a value, e.g. for optional filters.
+    pub fn append_opt(self, key: &str, value: Option<impl Display>) -> Self {
+        match value {
+            Some(value) => self.append(key, value),
+            None => self,
+        }
+    }
+
+    /// Replaces every `key` parameter with `key=value`.
+    pub fn set(mut self, key: &str, value: impl Display) -> Self {
+        self.pairs.retain(|pair| {
+            let decoded = form_urlencoded::parse(pair.as_bytes()).next();
+            decoded.is_none_or(|(k, _)| k != key)
+        });
+        self.append(key, value)
+    }
+
+    pub fn build(&self) -> String {
+        let mut out = self.base.clone();
+        if !self.pairs.is_empty() {
+            out.push('?');
+            out.push_str(&self.pairs.join("&"));
+        }
+        if let Some(fragment) = &self.fragment {
+            out.push('#');
+            out.push_str(fragment);
+        }
+        out
+    }
+}
+
+/// All values given for one key.
+struct Values(Vec<String>);
+
+impl<'de> IntoDeserializer<'de, DeError> for Values {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl Values {
+    fn last(mut self) -> Scalar {
+        Scalar(self.0.pop().unwrap_or_default())
+    }
+}
+
+macro_rules! forward_to_last {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                self.last().$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Values {
+    type Error = DeError;
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        SeqDeserializer::new(self.0.into_iter().map(Scalar)).deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.last().deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_last! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    serde::forward_to_deserialize_any! {
+        unit_struct tuple_struct struct
+    }
+}
+
+/// A single decoded value, parsed on demand into the type the field asks for.
+struct Scalar(String);
+
+impl<'de> IntoDeserializer<'de, DeError> for Scalar {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_scalar {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Scalar {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    parse_scalar! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_query, QueryBuilder};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Sort {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Filter {
+        #[serde(default)]
+        tag: Vec<String>,
+        #[serde(default)]
+        id: Vec<u32>,
+        page: Option<u32>,
+        sort: Option<Sort>,
+        q: Option<String>,
+    }
+
+    #[test]
+    fn it_collects_repeated_and_bracket_keys() {
+        let filter: Filter =
+            from_query("tag=a&tag=b&tag%5B%5D=c&id[]=1&id[]=2&page=1&page=3&sort=desc&q=a+b%21")
+                .unwrap();
+        assert_eq!(filter.tag, ["a", "b", "c"]);
+        assert_eq!(filter.id, [1, 2]);
+        assert_eq!(filter.page, Some(3));
+        assert_eq!(filter.sort, Some(Sort::Desc));
+        assert_eq!(filter.q.as_deref(), Some("a b!"));
+    }
+
+    #[test]
+    fn it_defaults_missing_fields() {
+        let filter: Filter = from_query("").unwrap();
+        assert!(filter.tag.is_empty());
+        assert_eq!(filter.page, None);
+
+        assert!(from_query::<Filter>("id=x").is_err());
+    }
+
+    #[test]
+    fn it_builds_outbound_queries() {
+        let url = QueryBuilder::new("/api/callback?step=1&page=1#done")
+            .set("page", 2)
+            .append("next", "/a b?c=d")
+            .append_opt("cursor", None::<&str>)
+            .build();
+        assert_eq!(url, "/api/callback?step=1&page=2&next=%2Fa+b%3Fc%3Dd#done");
+
+        assert_eq!(QueryBuilder::new("/api/callback").build(), "/api/callback");
+
+        let signed = "https://cdn.example.com/a?name=a%20b&x=%7E&sig=3x%2Fz";
+        let url = QueryBuilder::new(signed).append("n", 1).build();
+        assert_eq!(url, format!("{}&n=1", signed));
+        let url = QueryBuilder::new(signed).set("x", "~").build();
+        assert_eq!(url, "https://cdn.example.com/a?name=a%20b&sig=3x%2Fz&x=%7E");
+    }
+}
@@ -0,0 +1,105 @@
+//! `429 Too Many Requests` responses with `Retry-After` and `X-RateLimit-*` headers.
+use crate::{Body, IntoResponse};
+use lambda_http::{
+    http::{header, HeaderValue, StatusCode},
+    Response,
+};
+use std::time::{Duration, SystemTime};
+
+/// The two forms of `Retry-After` (RFC 9110, section 10.2.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAfter {
+    /// `Retry-After: <seconds>`, rounded up so clients never retry early.
+    Delay(Duration),
+    /// `Retry-After: <HTTP-date>`.
+    At(SystemTime),
+}
+
+impl RetryAfter {
+    pub fn header_value(&self) -> HeaderValue {
+        let value = match self {
+            RetryAfter::Delay(delay) => {
+                let secs = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+                secs.to_string()
+            }
+            RetryAfter::At(at) => httpdate::fmt_http_date(*at),
+        };
+        HeaderValue::try_from(value).expect("numbers and HTTP dates are valid header values")
+    }
+}
+
+/// A rejected request along with the state of the limit it exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    /// Requests allowed per window.
+    pub limit: u64,
+    /// Requests left in the current window.
+    pub remaining: u64,
+    /// Time until the window resets.
+    pub reset: Duration,
+    pub retry_after: RetryAfter,
+}
+
+impl RateLimited {
+    /// A rejection that may be retried once the window resets.
+    pub fn new(limit: u64, reset: Duration) -> Self {
+        RateLimited {
+            limit,
+            remaining: 0,
+            reset,
+            retry_after: RetryAfter::Delay(reset),
+        }
+    }
+
+    /// Overrides the `Retry-After` derived from `reset`.
+    pub fn retry_after(mut self, retry_after: RetryAfter) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+}
+
+/// `429` with `Retry-After`, `X-RateLimit-Limit`, `X-RateLimit-Remaining` and
+/// `X-RateLimit-Reset` (seconds until the reset).
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response<Body> {
+        let body = serde_json::json!({ "error": "too many requests" });
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::RETRY_AFTER, self.retry_after.header_value());
+        headers.insert("x-ratelimit-limit", self.limit.into());
+        headers.insert("x-ratelimit-remaining", self.remaining.into());
+        headers.insert(
+            "x-ratelimit-reset",
+            RetryAfter::Delay(self.reset).header_value(),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimited, RetryAfter};
+    use crate::IntoResponse;
+    use lambda_http::http::StatusCode;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn it_builds_rate_limited_responses() {
+        let response = RateLimited::new(100, Duration::from_millis(1500)).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers["retry-after"], "2");
+        assert_eq!(headers["x-ratelimit-limit"], "100");
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        assert_eq!(headers["x-ratelimit-reset"], "2");
+
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let response = RateLimited::new(100, Duration::from_secs(60))
+            .retry_after(RetryAfter::At(at))
+            .into_response();
+        assert_eq!(
+            response.headers()["retry-after"],
+            "Tue, 14 Nov 2023 22:13:20 GMT"
+        );
+    }
+}
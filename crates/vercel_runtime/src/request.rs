@@ -2,10 +2,58 @@ use base64::Engine;
 use lambda_http::http::{self, header::HeaderValue, HeaderMap, Method};
 use lambda_http::Body;
 use lambda_runtime::LambdaEvent;
-use serde::de::{Deserializer, Error as DeError, MapAccess, Visitor};
+use serde::de::{Deserializer, Error as DeError, IgnoredAny, MapAccess, Visitor};
 use serde::Deserialize;
 use serde_json::Value;
-use std::{borrow::Cow, fmt, mem};
+use std::{borrow::Cow, cell::Cell, fmt, mem};
+
+/// Marks a request that is answered with an error status without calling the handler.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rejection {
+    pub status: http::StatusCode,
+    pub reason: &'static str,
+}
+
+impl Rejection {
+    pub fn bad_request(reason: &'static str) -> Self {
+        Rejection {
+            status: http::StatusCode::BAD_REQUEST,
+            reason,
+        }
+    }
+}
+
+/// Byte limits for a single header (name and value) and for all headers combined.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HeaderLimits {
+    pub per_header: usize,
+    pub total: usize,
+}
+
+thread_local! {
+    // Decoding is synchronous, so these only live for one `decode` call
+    static HEADER_LIMITS: Cell<Option<HeaderLimits>> = const { Cell::new(None) };
+    static HEADER_OVERFLOW: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Decodes a proxy request, enforcing `limits` while its headers are read.
+///
+/// Once a limit is exceeded no further headers are kept, and the returned rejection answers
+/// `431 Request Header Fields Too Large`.
+pub(crate) fn decode(
+    body: &str,
+    limits: Option<HeaderLimits>,
+) -> serde_json::Result<(VercelRequest<'_>, Option<Rejection>)> {
+    HEADER_LIMITS.set(limits);
+    HEADER_OVERFLOW.set(None);
+    let request = serde_json::from_str(body);
+    HEADER_LIMITS.set(None);
+    let rejection = HEADER_OVERFLOW.take().map(|reason| Rejection {
+        status: http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+        reason,
+    });
+    request.map(|request| (request, rejection))
+}
 
 /// Representation of a Vercel Lambda proxy event data
 #[doc(hidden)]
@@ -88,13 +136,21 @@ where
             A: MapAccess<'de>,
         {
             let mut headers = http::HeaderMap::new();
+            let limits = HEADER_LIMITS.get();
+            let mut total = 0;
 
-            while let Some((key, value)) = map.next_entry::<&str, Value>()? {
+            while let Some(key) = map.next_key::<&str>()? {
+                if HEADER_OVERFLOW.get().is_some() {
+                    // Skip the remaining headers without decoding them
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+                let value = map.next_value::<Value>()?;
                 let header_name = key
                     .parse::<http::header::HeaderName>()
                     .map_err(A::Error::custom)?;
 
-                match value {
+                let header_value = match value {
                     Value::Object(o) => {
                         return Err(A::Error::custom(format!(
                             "unable to deserialize object inside headers: {:?}",
@@ -118,18 +174,29 @@ where
 
                         let joined_values = str_vec_values.join(",");
 
-                        headers.append(
-                            &header_name,
-                            HeaderValue::from_str(&joined_values).map_err(A::Error::custom)?,
-                        );
+                        HeaderValue::from_str(&joined_values).map_err(A::Error::custom)?
                     }
                     Value::Number(_) | Value::Bool(_) | Value::String(_) | Value::Null => {
-                        headers.append(
-                            &header_name,
-                            parse_scalar(&value).map_err(A::Error::custom)?,
-                        );
+                        parse_scalar(&value).map_err(A::Error::custom)?
                     }
                 };
+
+                if let Some(limits) = limits {
+                    let size = header_name.as_str().len() + header_value.len();
+                    total += size;
+                    let overflow = if size > limits.per_header {
+                        Some("request header too large")
+                    } else if total > limits.total {
+                        Some("request headers too large")
+                    } else {
+                        None
+                    };
+                    if overflow.is_some() {
+                        HEADER_OVERFLOW.set(overflow);
+                        continue;
+                    }
+                }
+                headers.append(&header_name, header_value);
             }
             Ok(headers)
         }
@@ -172,3 +239,42 @@ impl<'a> From<VercelRequest<'a>> for http::Request<Body> {
         req
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, HeaderLimits};
+    use lambda_http::http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn it_stops_decoding_headers_past_the_limits() {
+        let event = |headers| {
+            json!({ "host": "example.com", "path": "/", "method": "GET", "headers": headers })
+                .to_string()
+        };
+        let limits = Some(HeaderLimits {
+            per_header: 64,
+            total: 100,
+        });
+
+        let body = event(json!({ "x-a": "1", "x-big": "a".repeat(1000), "x-c": "2" }));
+        let (request, rejection) = decode(&body, limits).unwrap();
+        let rejection = rejection.unwrap();
+        assert_eq!(
+            rejection.status,
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        );
+        assert_eq!(rejection.reason, "request header too large");
+        assert_eq!(request.headers.len(), 1);
+        assert!(!request.headers.contains_key("x-big"));
+
+        let body = event(json!({ "x-a": "a".repeat(60), "x-b": "b".repeat(60) }));
+        let (request, rejection) = decode(&body, limits).unwrap();
+        assert_eq!(rejection.unwrap().reason, "request headers too large");
+        assert_eq!(request.headers.len(), 1);
+
+        let (request, rejection) = decode(&body, None).unwrap();
+        assert!(rejection.is_none());
+        assert_eq!(request.headers.len(), 2);
+    }
+}
@@ -1,9 +1,12 @@
+use crate::body::Body as VercelBody;
 use lambda_http::http::{
-    header::{HeaderMap, HeaderValue},
-    Response,
+    header::{self, HeaderMap, HeaderValue},
+    response::Parts,
+    Response, StatusCode,
 };
-use lambda_http::Body;
+use lambda_http::{Body, Error};
 use serde::Serialize;
+use tracing::warn;
 
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -31,22 +34,70 @@ impl Default for EventResponse {
     }
 }
 
-impl<T> From<Response<T>> for EventResponse
+/// Fails for streaming bodies, which have to be buffered first, see [`VercelBody::into_bytes`].
+impl<T> TryFrom<Response<T>> for EventResponse
 where
-    T: Into<Body>,
+    T: Into<VercelBody>,
 {
-    fn from(value: Response<T>) -> Self {
+    type Error = Error;
+
+    fn try_from(value: Response<T>) -> Result<Self, Error> {
         let (parts, bod) = value.into_parts();
         let (encoding, body) = match bod.into() {
-            Body::Empty => (None, None),
-            b @ Body::Text(_) => (None, Some(b)),
-            b @ Body::Binary(_) => (Some("base64".to_string()), Some(b)),
+            VercelBody::Empty => (None, None),
+            VercelBody::Text(t) => (None, Some(Body::Text(t))),
+            VercelBody::Binary(b) => (Some("base64".to_string()), Some(Body::Binary(b))),
+            VercelBody::Stream(_) => {
+                return Err("streaming bodies cannot be sent as a buffered response".into())
+            }
         };
-        EventResponse {
+        Ok(EventResponse {
             status_code: parts.status.as_u16(),
             body,
             headers: parts.headers,
             encoding,
-        }
+        })
+    }
+}
+
+/// Whether responses with this status must not carry a body (RFC 9110, section 6.4.1).
+pub fn forbids_body(status: StatusCode) -> bool {
+    status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+}
+
+/// Drops the body and framing headers of responses whose status forbids a body.
+pub(crate) fn enforce_empty_body(parts: &mut Parts, body: VercelBody) -> VercelBody {
+    if !forbids_body(parts.status) {
+        return body;
+    }
+
+    if !matches!(body, VercelBody::Empty) {
+        warn!(
+            "Handler returned a body with status {}, the body is omitted",
+            parts.status
+        );
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::TRANSFER_ENCODING);
+    VercelBody::Empty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventResponse;
+    use crate::body::Body;
+    use bytes::Bytes;
+    use lambda_http::http::Response;
+
+    #[test]
+    fn it_refuses_to_buffer_streaming_bodies() {
+        let response = EventResponse::try_from(Response::new(Body::from("done"))).unwrap();
+        assert_eq!(response.status_code, 200);
+
+        let chunks = futures_util::stream::iter([Ok::<_, std::io::Error>(Bytes::from("done"))]);
+        let response = Response::new(Body::from_stream(chunks));
+        assert!(EventResponse::try_from(response).is_err());
     }
 }
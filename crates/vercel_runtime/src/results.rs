@@ -0,0 +1,92 @@
+//! Collecting the outputs of workflow nodes in graph order.
+//!
+//! Nodes that run concurrently finish in any order. [`OrderedResults`] keys every output by the
+//! index of its node, so the assembled array follows the graph and not the completion order.
+use std::{collections::BTreeMap, fmt, sync::Mutex};
+
+/// A second output was recorded for the same node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultConflict {
+    pub index: usize,
+}
+
+impl fmt::Display for ResultConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {} already has a result", self.index)
+    }
+}
+
+impl std::error::Error for ResultConflict {}
+
+/// Node outputs keyed by node index, shareable between concurrent steps (e.g. in an `Arc`).
+#[derive(Debug, Default)]
+pub struct OrderedResults<T> {
+    results: Mutex<BTreeMap<usize, T>>,
+}
+
+impl<T> OrderedResults<T> {
+    pub fn new() -> Self {
+        OrderedResults {
+            results: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records the output of the node at `index`, refusing to replace an earlier one.
+    pub fn insert(&self, index: usize, result: T) -> Result<(), ResultConflict> {
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        if results.contains_key(&index) {
+            return Err(ResultConflict { index });
+        }
+        results.insert(index, result);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.lock().map_or(0, |results| results.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Indices below `count` that have no result yet.
+    pub fn missing(&self, count: usize) -> Vec<usize> {
+        let results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        (0..count).filter(|i| !results.contains_key(i)).collect()
+    }
+
+    /// The outputs in node order.
+    pub fn into_vec(self) -> Vec<T> {
+        let results = self.results.into_inner().unwrap_or_else(|e| e.into_inner());
+        results.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrderedResults, ResultConflict};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn it_orders_results_by_node() {
+        let results = Arc::new(OrderedResults::new());
+        let tasks: Vec<_> = [2, 0, 1]
+            .into_iter()
+            .map(|index| {
+                let results = results.clone();
+                tokio::spawn(async move { results.insert(index, format!("node {}", index)) })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(
+            results.insert(1, "again".to_owned()),
+            Err(ResultConflict { index: 1 })
+        );
+        assert_eq!(results.missing(4), [3]);
+        let results = Arc::try_unwrap(results).unwrap();
+        assert_eq!(results.into_vec(), ["node 0", "node 1", "node 2"]);
+    }
+}
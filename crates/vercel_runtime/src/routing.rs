@@ -0,0 +1,45 @@
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+
+/// Route template the `bundled_api` router dispatched to, e.g. `/api/step-v4/[step]`.
+///
+/// Inserted into the request extensions by the generated handler, read it through
+/// [`crate::RequestExt::matched_route`].
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchedRoute(pub &'static str);
+
+/// Segments of a request path, see [`crate::RequestExt::path_segments`].
+#[derive(Debug, Clone)]
+pub struct PathSegments<'a> {
+    segments: std::str::Split<'a, char>,
+}
+
+impl<'a> PathSegments<'a> {
+    pub(crate) fn new(path: &'a str) -> Self {
+        PathSegments {
+            segments: path.split('/'),
+        }
+    }
+}
+
+impl<'a> Iterator for PathSegments<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let segment = self.segments.find(|segment| !segment.is_empty())?;
+        Some(percent_decode_str(segment).decode_utf8_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PathSegments;
+
+    #[test]
+    fn it_splits_before_decoding() {
+        let segments: Vec<_> = PathSegments::new("/api//files/a%2Fb/caf%C3%A9/").collect();
+        assert_eq!(segments, ["api", "files", "a/b", "café"]);
+        assert_eq!(PathSegments::new("/").count(), 0);
+    }
+}
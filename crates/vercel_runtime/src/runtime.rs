@@ -0,0 +1,226 @@
+//! Process wide runtime state.
+use crate::rate_limit::RetryAfter;
+use crate::utils::random_u64;
+use crate::Body;
+use lambda_http::http::{header, StatusCode};
+use lambda_http::{Error, Response};
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Env var that declares the function was deployed with response streaming enabled.
+pub const STREAMING_ENV: &str = "VERCEL_RUST_STREAMING";
+
+static STREAMING: AtomicBool = AtomicBool::new(false);
+
+/// Whether responses can be streamed to the client.
+///
+/// The invocation itself does not tell the function whether it was deployed with streaming
+/// enabled, so this has to be declared to match the function configuration, either through
+/// [`crate::RuntimeConfig::streaming`] or by setting `VERCEL_RUST_STREAMING=1`.
+pub fn supports_streaming() -> bool {
+    static FROM_ENV: OnceLock<bool> = OnceLock::new();
+
+    STREAMING.load(Ordering::Relaxed)
+        || *FROM_ENV.get_or_init(|| {
+            std::env::var(STREAMING_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        })
+}
+
+pub(crate) fn set_streaming(enabled: bool) {
+    STREAMING.store(enabled, Ordering::Relaxed);
+}
+
+/// Env var that starts the function in maintenance mode when set to `1` or `true`.
+pub const MAINTENANCE_ENV: &str = "MAINTENANCE";
+
+/// How requests are answered in maintenance mode, see [`set_maintenance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceInfo {
+    message: String,
+    retry_after: Option<RetryAfter>,
+    allow: Vec<String>,
+}
+
+impl Default for MaintenanceInfo {
+    fn default() -> Self {
+        MaintenanceInfo {
+            message: "down for maintenance".to_owned(),
+            retry_after: None,
+            allow: Vec::new(),
+        }
+    }
+}
+
+impl MaintenanceInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `error` of the `{"error": "..."}` body.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn retry_after(mut self, retry_after: RetryAfter) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Keeps serving `path` and everything below it, e.g. `/api/health`.
+    pub fn allow(mut self, path: impl Into<String>) -> Self {
+        self.allow.push(path.into());
+        self
+    }
+
+    /// Whether requests for `path` bypass maintenance mode.
+    pub fn allows(&self, path: &str) -> bool {
+        self.allow.iter().any(|allowed| {
+            let allowed = allowed.trim_end_matches('/');
+            path.strip_prefix(allowed)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// The `503 Service Unavailable` sent instead of calling the handler.
+    pub fn response(&self) -> Result<Response<Body>, Error> {
+        let mut builder = Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CACHE_CONTROL, "no-store");
+        if let Some(retry_after) = &self.retry_after {
+            builder = builder.header(header::RETRY_AFTER, retry_after.header_value());
+        }
+        let body = serde_json::json!({ "error": self.message });
+        Ok(builder.body(Body::Text(body.to_string()))?)
+    }
+}
+
+fn maintenance_state() -> &'static Mutex<Option<MaintenanceInfo>> {
+    static STATE: OnceLock<Mutex<Option<MaintenanceInfo>>> = OnceLock::new();
+    STATE.get_or_init(|| {
+        let enabled = std::env::var(MAINTENANCE_ENV)
+            .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        Mutex::new(enabled.then(MaintenanceInfo::default))
+    })
+}
+
+/// Switches maintenance mode on or off for this instance.
+///
+/// While on, [`crate::run`] answers every request outside of the allowed paths with the
+/// [`MaintenanceInfo::response`]. It starts on when `MAINTENANCE=1` is set, with the default
+/// message and no allowed paths. Changing env vars requires a redeployment on Vercel, so flip
+/// it from a check against a shared store to switch running instances.
+pub fn set_maintenance(info: Option<MaintenanceInfo>) {
+    if let Ok(mut state) = maintenance_state().lock() {
+        *state = info;
+    }
+}
+
+/// The current maintenance mode, `None` when off.
+pub fn maintenance() -> Option<MaintenanceInfo> {
+    maintenance_state()
+        .lock()
+        .ok()
+        .and_then(|state| state.clone())
+}
+
+thread_local! {
+    /// The frozen time and the last id handed out while frozen
+    static FROZEN: Cell<Option<(SystemTime, u64)>> = const { Cell::new(None) };
+}
+
+/// The current time, used by the timestamping helpers of the crate (e.g. completion objects,
+/// signed tokens and webhook tolerances).
+///
+/// Returns the frozen time on threads where [`freeze_time`] is active.
+pub fn now() -> SystemTime {
+    FROZEN
+        .with(Cell::get)
+        .map_or_else(SystemTime::now, |(at, _)| at)
+}
+
+/// Freezes [`now`] at `at` on the current thread until the returned guard is dropped.
+///
+/// While frozen, generated ids (e.g. `chatcmpl-` ids) count up from 1 instead of being random,
+/// so tests can assert on them. `#[tokio::test]` runs on the current thread by default, tasks
+/// moved to other threads see the real clock.
+pub fn freeze_time(at: SystemTime) -> FrozenTime {
+    let previous = FROZEN.with(|frozen| frozen.replace(Some((at, 0))));
+    FrozenTime { previous }
+}
+
+/// Guard of [`freeze_time`], restores the previous clock when dropped.
+#[derive(Debug)]
+pub struct FrozenTime {
+    previous: Option<(SystemTime, u64)>,
+}
+
+impl FrozenTime {
+    /// Moves the frozen time forward.
+    pub fn advance(&self, by: Duration) {
+        FROZEN.with(|frozen| {
+            if let Some((at, id)) = frozen.get() {
+                frozen.set(Some((at + by, id)));
+            }
+        });
+    }
+}
+
+impl Drop for FrozenTime {
+    fn drop(&mut self) {
+        FROZEN.with(|frozen| frozen.set(self.previous));
+    }
+}
+
+/// A random id, or the next one of the sequence while the time is frozen.
+pub(crate) fn unique_id() -> u64 {
+    FROZEN.with(|frozen| match frozen.get() {
+        Some((at, id)) => {
+            frozen.set(Some((at, id + 1)));
+            id + 1
+        }
+        None => random_u64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{freeze_time, now, unique_id, MaintenanceInfo};
+    use crate::rate_limit::RetryAfter;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn it_answers_maintenance_with_503() {
+        let info = MaintenanceInfo::new()
+            .retry_after(RetryAfter::Delay(Duration::from_secs(120)))
+            .allow("/api/health/");
+        assert!(info.allows("/api/health"));
+        assert!(info.allows("/api/health/db"));
+        assert!(!info.allows("/api/healthz"));
+        assert!(!info.allows("/api/step-v4/1"));
+
+        let response = info.response().unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()["retry-after"], "120");
+    }
+
+    #[test]
+    fn it_freezes_time_and_ids() {
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        {
+            let clock = freeze_time(at);
+            assert_eq!(now(), at);
+            clock.advance(Duration::from_secs(5));
+            assert_eq!(now(), at + Duration::from_secs(5));
+            assert_eq!((unique_id(), unique_id()), (1, 2));
+        }
+        assert!(now() > at + Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,200 @@
+//! Tamper proof, expiring tokens for values handed to the client, e.g. the next step of a
+//! redirect chain.
+//!
+//! A token is `base64url(json) . base64url(hmac_sha256(json))`, where the JSON holds the
+//! payload and its expiry. The payload is readable by the client, only sign what it may see.
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use vercel_runtime::signing;
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct Step { workflow_id: String, step: u32 }
+//! let step = Step { workflow_id: "wf_1".into(), step: 2 };
+//! let token = signing::sign(&step, b"secret", Duration::from_secs(60)).unwrap();
+//! let location = signing::with_token("/api/step-v4/2", &token);
+//!
+//! let token = signing::token_from_url(&location).unwrap();
+//! let step: Step = signing::verify(&token, b"secret").unwrap();
+//! assert_eq!(step.step, 2);
+//! ```
+use crate::runtime;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use lambda_http::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Query parameter used by [`with_token`] and [`token_from_url`].
+pub const TOKEN_PARAM: &str = "token";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a token was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// Not a token produced by [`sign`], or a payload of another type.
+    Malformed,
+    /// The signature does not match, the token was altered or signed with another secret.
+    InvalidSignature,
+    Expired,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VerifyError::Malformed => "malformed token",
+            VerifyError::InvalidSignature => "invalid token signature",
+            VerifyError::Expired => "token expired",
+        })
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+#[derive(Serialize)]
+struct Claims<'a, T> {
+    exp: u64,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct OwnedClaims<T> {
+    exp: u64,
+    data: T,
+}
+
+/// Signs `payload`, the token is valid for `expires_in`.
+pub fn sign<T: Serialize>(
+    payload: &T,
+    secret: &[u8],
+    expires_in: Duration,
+) -> Result<String, Error> {
+    let exp = unix_now().saturating_add(expires_in.as_secs());
+    let claims = serde_json::to_vec(&Claims { exp, data: payload })?;
+    let signature = mac(secret, &claims).finalize().into_bytes();
+    Ok(format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(claims),
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
+
+/// Checks the signature and expiry of `token` and returns its payload.
+pub fn verify<T: DeserializeOwned>(token: &str, secret: &[u8]) -> Result<T, VerifyError> {
+    let (claims, signature) = token.split_once('.').ok_or(VerifyError::Malformed)?;
+    let claims = URL_SAFE_NO_PAD
+        .decode(claims)
+        .map_err(|_| VerifyError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| VerifyError::Malformed)?;
+
+    // Compares in constant time
+    mac(secret, &claims)
+        .verify_slice(&signature)
+        .map_err(|_| VerifyError::InvalidSignature)?;
+
+    let claims: OwnedClaims<T> =
+        serde_json::from_slice(&claims).map_err(|_| VerifyError::Malformed)?;
+    if claims.exp <= unix_now() {
+        return Err(VerifyError::Expired);
+    }
+    Ok(claims.data)
+}
+
+/// Appends `token` to `location` as the [`TOKEN_PARAM`] query parameter.
+pub fn with_token(location: &str, token: &str) -> String {
+    let (location, fragment) = match location.split_once('#') {
+        Some((location, fragment)) => (location, Some(fragment)),
+        None => (location, None),
+    };
+    let separator = match location.contains('?') {
+        true => '&',
+        false => '?',
+    };
+    // Tokens only contain URL safe characters, no encoding needed
+    let mut url = format!("{}{}{}={}", location, separator, TOKEN_PARAM, token);
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// Reads the [`TOKEN_PARAM`] query parameter from a URL, a path with query or a bare query.
+pub fn token_from_url(url: &str) -> Option<String> {
+    let query = url.split('#').next()?;
+    let query = query.split_once('?').map_or(query, |(_, q)| q);
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == TOKEN_PARAM)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn mac(secret: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(message);
+    mac
+}
+
+fn unix_now() -> u64 {
+    runtime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, token_from_url, verify, with_token, VerifyError};
+    use std::time::Duration;
+
+    const SECRET: &[u8] = b"hunter2";
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn it_rejects_tampered_tokens() {
+        let token = sign(&("wf_1", 2), SECRET, MINUTE).unwrap();
+        assert_eq!(verify::<(String, u32)>(&token, SECRET).unwrap().1, 2);
+        assert_eq!(
+            verify::<(String, u32)>(&token, b"other"),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        let forged = sign(&("wf_1", 9), b"other", MINUTE).unwrap();
+        let (claims, _) = forged.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        assert_eq!(
+            verify::<(String, u32)>(&format!("{}.{}", claims, signature), SECRET),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_eq!(
+            verify::<u32>("garbage", SECRET),
+            Err(VerifyError::Malformed)
+        );
+    }
+
+    #[test]
+    fn it_rejects_expired_tokens() {
+        let token = sign(&1, SECRET, Duration::ZERO).unwrap();
+        assert_eq!(verify::<u32>(&token, SECRET), Err(VerifyError::Expired));
+    }
+
+    #[test]
+    fn it_embeds_tokens_in_locations() {
+        assert_eq!(with_token("/api/step/2", "abc"), "/api/step/2?token=abc");
+        assert_eq!(
+            with_token("/next?a=1#top", "abc"),
+            "/next?a=1&token=abc#top"
+        );
+        assert_eq!(
+            token_from_url("https://example.com/next?a=1&token=abc#top").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(token_from_url("a=1&token=abc").as_deref(), Some("abc"));
+        assert_eq!(token_from_url("/next"), None);
+    }
+}
@@ -0,0 +1,610 @@
+use crate::{
+    runtime,
+    streaming::{self, BodySender, FlushPolicy, HeadersMut},
+    Body,
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use lambda_http::http::{header, response::Builder, Response};
+use lambda_http::Error;
+use serde_json::json;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Code of the `error` events the runtime sends for failures it catches itself.
+const INTERNAL_CODE: &str = "internal";
+
+/// A single server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    pub retry: Option<Duration>,
+}
+
+impl SseEvent {
+    /// An unnamed event carrying `data`.
+    pub fn data(data: impl Into<String>) -> Self {
+        SseEvent {
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    /// An `error` event with the `{"code": ..., "message": ...}` payload of the client
+    /// contract, see [`SseResponse`].
+    pub fn error(code: &str, message: impl AsRef<str>) -> Self {
+        let data = json!({ "code": code, "message": message.as_ref() });
+        SseEvent::data(data.to_string()).event("error")
+    }
+
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Serializes the event in the `text/event-stream` format.
+    ///
+    /// Line breaks in `data` (CRLF, CR or LF) are sent as separate `data:` lines, line breaks
+    /// in `id` and `event` are dropped since they would end the field early.
+    pub fn encode(&self) -> Bytes {
+        self.encode_with(&SseFormat::default())
+    }
+
+    /// Like [`SseEvent::encode`] with a custom data field and separator.
+    pub fn encode_with(&self, format: &SseFormat) -> Bytes {
+        let ending = format.line_ending();
+        let mut out = String::new();
+        if let Some(id) = &self.id {
+            push_field(&mut out, "id", &single_line(id), ending);
+        }
+        if let Some(event) = &self.event {
+            push_field(&mut out, "event", &single_line(event), ending);
+        }
+        if let Some(retry) = self.retry {
+            push_field(&mut out, "retry", &retry.as_millis().to_string(), ending);
+        }
+        // Clients end a line at CRLF, a lone CR or a lone LF
+        for line in self
+            .data
+            .split("\r\n")
+            .flat_map(|line| line.split(['\r', '\n']))
+        {
+            push_field(&mut out, &format.data_field, line, ending);
+        }
+        // The last field already ended its line
+        out.push_str(&format.separator[ending.len()..]);
+        Bytes::from(out)
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+fn push_field(out: &mut String, name: &str, value: &str, ending: &str) {
+    out.push_str(name);
+    out.push_str(": ");
+    out.push_str(value);
+    out.push_str(ending);
+}
+
+/// The framing of [`SseEvent`]s, for clients that expect something else than the spec.
+///
+/// Defaults to the spec: data in `data:` fields and a blank line after every event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseFormat {
+    data_field: String,
+    separator: String,
+}
+
+impl Default for SseFormat {
+    fn default() -> Self {
+        SseFormat {
+            data_field: "data".to_owned(),
+            separator: "\n\n".to_owned(),
+        }
+    }
+}
+
+impl SseFormat {
+    /// Sends data in `data_field:` lines and ends every event with `separator`, e.g.
+    /// `SseFormat::new("message", "\r\n\r\n")`.
+    ///
+    /// Fails for a field name that is empty or contains `:` or a line break, since it would
+    /// end the field early. The separator may only contain line breaks and must leave a blank
+    /// line after the event, so no data line can be taken for the end of an event. Its first
+    /// line break is used to end every line.
+    pub fn new(data_field: &str, separator: &str) -> Result<Self, Error> {
+        if data_field.is_empty() || data_field.contains([':', '\r', '\n']) {
+            return Err(Error::from(format!(
+                "invalid SSE field name {:?}",
+                data_field
+            )));
+        }
+        let blank_line = ["\n\n", "\r\n\r\n", "\n\r\n", "\r\n\n"]
+            .iter()
+            .any(|start| separator.starts_with(start));
+        if !blank_line || separator.contains(|c| c != '\r' && c != '\n') {
+            return Err(Error::from(format!(
+                "invalid SSE event separator {:?}, it has to be line breaks ending in a blank line",
+                separator
+            )));
+        }
+        Ok(SseFormat {
+            data_field: data_field.to_owned(),
+            separator: separator.to_owned(),
+        })
+    }
+
+    /// A comment, ignored by clients but keeping the connection busy.
+    fn keep_alive(&self) -> String {
+        format!(": keep-alive{}", self.separator)
+    }
+
+    fn line_ending(&self) -> &str {
+        match self.separator.starts_with("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        }
+    }
+}
+
+/// Imperative writer for a `text/event-stream` response.
+///
+/// Once streaming started the status can no longer change, so failures are reported in-band:
+/// [`SseResponse::send_error`] sends a final `error` event whose data is
+/// `{"code": "...", "message": "..."}` and closes the stream. Failures the runtime catches
+/// itself (a handler stream error or a panicking producer) are sent the same way with the
+/// code `internal`. Clients should treat an `error` event as terminal and not wait for further
+/// events.
+///
+/// ```no_run
+/// # use vercel_runtime::{sse::{SseEvent, SseResponse}, Body, Error, Response};
+/// # async fn handler() -> Result<Response<Body>, Error> {
+/// let (mut sse, response) = SseResponse::new(Response::builder())?;
+/// tokio::spawn(async move { sse.send(SseEvent::data("hello")).await });
+/// Ok(response)
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SseResponse {
+    sender: BodySender,
+    format: SseFormat,
+}
+
+impl SseResponse {
+    /// Creates the writer and the response to return from the handler.
+    ///
+    /// Fails when streaming is not enabled for the function (see
+    /// [`runtime::supports_streaming`]), since every event would otherwise be held back until
+    /// the stream ends.
+    pub fn new(builder: Builder) -> Result<(Self, Response<Body>), Error> {
+        Self::with_streaming(builder, runtime::supports_streaming())
+    }
+
+    fn with_streaming(builder: Builder, streaming: bool) -> Result<(Self, Response<Body>), Error> {
+        if !streaming {
+            return Err(Error::from(
+                "SseResponse requires response streaming, enable it with \
+                 RuntimeConfig::streaming(true) or VERCEL_RUST_STREAMING=1",
+            ));
+        }
+
+        let (mut sender, body) = streaming::channel();
+        let response = builder
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache, no-transform")
+            .extension(sender.attach_headers())
+            .body(body)?;
+        Ok((
+            SseResponse {
+                sender,
+                format: SseFormat::default(),
+            },
+            response,
+        ))
+    }
+
+    /// Streams events produced by blocking code, e.g. CPU-bound work.
+    ///
+    /// The iterator runs on tokio's blocking pool and waits whenever the client falls behind.
+    /// It stops early once the client goes away. A panic in the iterator ends the stream with
+    /// an `error` event.
+    pub fn from_blocking_iter<I>(builder: Builder, iter: I) -> Result<Response<Body>, Error>
+    where
+        I: IntoIterator<Item = SseEvent> + Send + 'static,
+    {
+        Self::from_blocking_iter_with_format(builder, iter, SseFormat::default())
+    }
+
+    /// [`SseResponse::from_blocking_iter`] with a custom [`SseFormat`].
+    pub fn from_blocking_iter_with_format<I>(
+        builder: Builder,
+        iter: I,
+        format: SseFormat,
+    ) -> Result<Response<Body>, Error>
+    where
+        I: IntoIterator<Item = SseEvent> + Send + 'static,
+    {
+        let (sse, response) = SseResponse::new(builder)?;
+        let mut sse = sse.with_format(format);
+        let mut sender = sse.sender.clone();
+        let format = sse.format.clone();
+
+        let producer = tokio::task::spawn_blocking(move || {
+            for event in iter {
+                if sender.blocking_send(event.encode_with(&format)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            if let Err(e) = producer.await {
+                let message = match e.try_into_panic() {
+                    Ok(panic) => panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "event producer panicked".to_owned()),
+                    Err(e) => e.to_string(),
+                };
+                let _ = sse.send(SseEvent::error(INTERNAL_CODE, message)).await;
+            }
+        });
+
+        Ok(response)
+    }
+
+    /// Frames the events sent from now on with `format` instead of the spec, for clients that
+    /// expect e.g. `message:` lines.
+    pub fn with_format(mut self, format: SseFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub async fn send(&mut self, event: SseEvent) -> Result<(), Error> {
+        self.sender.send(event.encode_with(&self.format)).await
+    }
+
+    /// Streams `events` as the response, driving the write loop on a spawned task.
+    ///
+    /// The first error is sent as a terminal `error` event. With `keep_alive` set, a comment
+    /// line is sent whenever no event arrived for that long, so proxies do not close an idle
+    /// connection. The loop stops early once the client goes away.
+    ///
+    /// ```no_run
+    /// # use futures_util::stream;
+    /// # use std::time::Duration;
+    /// # use vercel_runtime::{sse::{SseEvent, SseResponse}, Body, Error, Response};
+    /// # async fn handler() -> Result<Response<Body>, Error> {
+    /// let tokens = stream::iter(["Hel", "lo"].map(|token| Ok::<_, Error>(SseEvent::data(token))));
+    /// SseResponse::from_stream(Response::builder(), tokens, Some(Duration::from_secs(15)))
+    /// # }
+    /// ```
+    pub fn from_stream<S, E>(
+        builder: Builder,
+        events: S,
+        keep_alive: Option<Duration>,
+    ) -> Result<Response<Body>, Error>
+    where
+        S: Stream<Item = Result<SseEvent, E>> + Send + 'static,
+        E: Into<Error> + Send,
+    {
+        Self::from_stream_with_format(builder, events, keep_alive, SseFormat::default())
+    }
+
+    /// [`SseResponse::from_stream`] with a custom [`SseFormat`], also used for the keep-alive
+    /// comments.
+    pub fn from_stream_with_format<S, E>(
+        builder: Builder,
+        events: S,
+        keep_alive: Option<Duration>,
+        format: SseFormat,
+    ) -> Result<Response<Body>, Error>
+    where
+        S: Stream<Item = Result<SseEvent, E>> + Send + 'static,
+        E: Into<Error> + Send,
+    {
+        let (sse, response) = SseResponse::new(builder)?;
+        let mut sse = sse.with_format(format);
+        let keep_alive_comment = sse.format.keep_alive();
+
+        tokio::spawn(async move {
+            let mut events = std::pin::pin!(events);
+            loop {
+                let next = events.next();
+                let next = match keep_alive {
+                    Some(interval) => match tokio::time::timeout(interval, next).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            if sse.sender.send(keep_alive_comment.clone()).await.is_err() {
+                                return;
+                            }
+                            continue;
+                        }
+                    },
+                    None => next.await,
+                };
+                let (event, last) = match next {
+                    Some(Ok(event)) => (event, false),
+                    Some(Err(e)) => (SseEvent::error(INTERNAL_CODE, e.into().to_string()), true),
+                    None => return,
+                };
+                if sse.send(event).await.is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        Ok(response)
+    }
+
+    /// Headers to add to the response, e.g. from the spawned writer task.
+    ///
+    /// They replace headers of the same name set on the builder. Fails once the first event
+    /// was sent or the response headers went out, since later changes would not reach the
+    /// client.
+    pub fn headers_mut(&self) -> Result<HeadersMut<'_>, Error> {
+        self.sender.headers_mut()
+    }
+
+    /// Events are flushed one by one by default, see [`BodySender::set_flush_policy`].
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        self.sender.set_flush_policy(policy);
+    }
+
+    /// Reports a failure to the client and ends the stream, see the client contract above.
+    pub async fn send_error(mut self, code: &str, message: &str) -> Result<(), Error> {
+        self.send(SseEvent::error(code, message)).await
+    }
+
+    /// Shorthand for sending an unnamed event.
+    pub async fn send_data(&mut self, data: impl Into<String>) -> Result<(), Error> {
+        self.send(SseEvent::data(data)).await
+    }
+}
+
+/// Turns a stream of events into an SSE body stream.
+///
+/// ```no_run
+/// # use futures_util::{stream, StreamExt};
+/// # use vercel_runtime::{sse::{SseEvent, SseStreamExt}, Body, Error};
+/// let upstream = stream::iter(vec![Ok::<_, Error>("token")]);
+/// let body = Body::from_stream(
+///     upstream
+///         .map(|chunk| chunk.map(|token| SseEvent::data(token.to_uppercase())))
+///         .into_sse(),
+/// );
+/// ```
+pub trait SseStreamExt: Stream + Sized {
+    /// Encodes every event; the first error is sent as a terminal `error` event.
+    fn into_sse(self) -> SseStream<Self> {
+        self.into_sse_with_format(SseFormat::default())
+    }
+
+    /// [`SseStreamExt::into_sse`] with a custom [`SseFormat`].
+    fn into_sse_with_format(self, format: SseFormat) -> SseStream<Self> {
+        SseStream {
+            inner: self,
+            format,
+            done: false,
+        }
+    }
+}
+
+impl<S, E> SseStreamExt for S
+where
+    S: Stream<Item = Result<SseEvent, E>>,
+    E: Into<Error>,
+{
+}
+
+/// See [`SseStreamExt::into_sse`].
+pub struct SseStream<S> {
+    inner: S,
+    format: SseFormat,
+    done: bool,
+}
+
+impl<S, E> Stream for SseStream<S>
+where
+    S: Stream<Item = Result<SseEvent, E>> + Unpin,
+    E: Into<Error>,
+{
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => Poll::Ready(Some(Ok(event.encode_with(&self.format)))),
+            Poll::Ready(Some(Err(e))) => {
+                self.done = true;
+                let error: Error = e.into();
+                Poll::Ready(Some(Ok(
+                    SseEvent::error(INTERNAL_CODE, error.to_string()).encode_with(&self.format)
+                )))
+            }
+            Poll::Ready(None) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SseEvent, SseFormat, SseResponse, SseStreamExt};
+    use crate::Body;
+    use futures_util::{stream, StreamExt};
+    use lambda_http::{Error, Response};
+    use std::time::Duration;
+
+    #[test]
+    fn it_requires_streaming() {
+        // Checked against the flag directly, other tests enable streaming process-wide
+        let error = SseResponse::with_streaming(Response::builder(), false).unwrap_err();
+        assert!(error.to_string().contains("requires response streaming"));
+        let (_, response) = SseResponse::with_streaming(Response::builder(), true).unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+    }
+
+    #[tokio::test]
+    async fn it_reports_panics_of_blocking_producers() {
+        crate::runtime::set_streaming(true);
+
+        let events = (0..3).map(|i| {
+            if i == 2 {
+                panic!("boom");
+            }
+            SseEvent::data(i.to_string())
+        });
+        let response = SseResponse::from_blocking_iter(Response::builder(), events).unwrap();
+
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            "data: 0\n\ndata: 1\n\nevent: error\ndata: {\"code\":\"internal\",\"message\":\"boom\"}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_ends_the_stream_after_an_error() {
+        crate::runtime::set_streaming(true);
+
+        let (mut sse, response) = SseResponse::new(Response::builder()).unwrap();
+        tokio::spawn(async move {
+            sse.send_data("partial").await.unwrap();
+            sse.send_error("upstream_failed", "node 3 failed")
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            "data: partial\n\nevent: error\ndata: {\"code\":\"upstream_failed\",\"message\":\"node 3 failed\"}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_drives_event_streams_with_keep_alive() {
+        crate::runtime::set_streaming(true);
+
+        let slow = stream::iter(vec![
+            Ok::<_, Error>(SseEvent::data("first")),
+            Err(Error::from("upstream failed")),
+        ])
+        .then(|event| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            event
+        });
+        let response =
+            SseResponse::from_stream(Response::builder(), slow, Some(Duration::from_millis(70)))
+                .unwrap();
+
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            ": keep-alive\n\ndata: first\n\n: keep-alive\n\nevent: error\ndata: {\"code\":\"internal\",\"message\":\"upstream failed\"}\n\n"
+        );
+    }
+
+    #[test]
+    fn it_encodes_events() {
+        let event = SseEvent::data("a\nb").event("tok\nen").id("1");
+        assert_eq!(event.encode(), "id: 1\nevent: token\ndata: a\ndata: b\n\n");
+
+        let event = SseEvent::data("a\rb\r\nc\r");
+        assert_eq!(event.encode(), "data: a\ndata: b\ndata: c\ndata: \n\n");
+    }
+
+    #[test]
+    fn it_encodes_custom_formats() {
+        let format = SseFormat::new("message", "\r\n\r\n").unwrap();
+        let event = SseEvent::data("a\nb").event("token");
+        assert_eq!(
+            event.encode_with(&format),
+            "event: token\r\nmessage: a\r\nmessage: b\r\n\r\n"
+        );
+
+        assert!(SseFormat::new("", "\n\n").is_err());
+        assert!(SseFormat::new("data:", "\n\n").is_err());
+        assert!(SseFormat::new("message", "\n").is_err());
+        assert!(SseFormat::new("message", "\n--\n").is_err());
+        assert!(SseFormat::new("message", "\n\n\n").is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_streams_with_custom_formats() {
+        crate::runtime::set_streaming(true);
+        let format = || SseFormat::new("message", "\r\n\r\n").unwrap();
+
+        let events = vec![SseEvent::data("a")];
+        let response =
+            SseResponse::from_blocking_iter_with_format(Response::builder(), events, format())
+                .unwrap();
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            "message: a\r\n\r\n"
+        );
+
+        let slow = stream::iter(vec![Ok::<_, Error>(SseEvent::data("b"))]).then(|event| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            event
+        });
+        let response = SseResponse::from_stream_with_format(
+            Response::builder(),
+            slow,
+            Some(Duration::from_millis(70)),
+            format(),
+        )
+        .unwrap();
+        assert_eq!(
+            response.into_body().into_string().await.unwrap(),
+            ": keep-alive\r\n\r\nmessage: b\r\n\r\n"
+        );
+
+        let upstream = stream::iter(vec![Ok::<_, Error>(SseEvent::data("c"))]);
+        let body = Body::from_stream(upstream.into_sse_with_format(format()));
+        assert_eq!(body.into_string().await.unwrap(), "message: c\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn it_maps_chunks_while_streaming() {
+        let upstream = stream::iter(vec![
+            Ok::<_, Error>("hello"),
+            Ok(" world"),
+            Err(Error::from("upstream failed")),
+            Ok("never sent"),
+        ]);
+
+        let body = Body::from_stream(
+            upstream
+                .map(|chunk| chunk.map(|token| SseEvent::data(token.to_uppercase())))
+                .into_sse(),
+        );
+
+        assert_eq!(
+            body.into_string().await.unwrap(),
+            "data: HELLO\n\ndata:  WORLD\n\nevent: error\ndata: {\"code\":\"internal\",\"message\":\"upstream failed\"}\n\n"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! A cap on redirect driven step chains, so a malformed graph cannot loop forever.
+//!
+//! Each step checks the depth with [`redirect_chain_guard`] and hands over to the next one
+//! with [`StepDepth::redirect`]:
+//!
+//! ```ignore
+//! let depth = match steps::redirect_chain_guard(&req, 32) {
+//!     Ok(depth) => depth,
+//!     Err(response) => return Ok(response),
+//! };
+//! // ...
+//! depth.redirect(&format!("/api/step-v4/{}", step + 1))
+//! ```
+//!
+//! Clients do not send headers of a redirect response back to its `Location`, so the depth
+//! travels in the `step_depth` query parameter. The [`STEP_DEPTH_HEADER`] is read as well, for
+//! callers that chain steps themselves, and set on every redirect.
+use crate::query::QueryBuilder;
+use crate::{http, Body};
+use lambda_http::http::header::HeaderValue;
+use lambda_http::{Error, Request, Response};
+
+pub const STEP_DEPTH_HEADER: &str = "x-step-depth";
+const STEP_DEPTH_PARAM: &str = "step_depth";
+
+/// Number of steps that led to the current request, `0` for the first one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StepDepth(pub u32);
+
+impl StepDepth {
+    /// Reads the depth of `req` from the query or the [`STEP_DEPTH_HEADER`], whichever is
+    /// larger. Missing or malformed values count as `0`.
+    pub fn of(req: &Request) -> StepDepth {
+        let query = req
+            .uri()
+            .query()
+            .and_then(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .find(|(key, _)| key == STEP_DEPTH_PARAM)
+                    .and_then(|(_, value)| value.parse().ok())
+            })
+            .unwrap_or(0);
+        let header = req
+            .headers()
+            .get(STEP_DEPTH_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0);
+        StepDepth(query.max(header))
+    }
+
+    /// A `307 Temporary Redirect` to the next step, one level deeper.
+    pub fn redirect(self, location: &str) -> Result<Response<Body>, Error> {
+        let next = self.0.saturating_add(1);
+        let mut response = http::temporary_redirect(
+            &QueryBuilder::new(location)
+                .set(STEP_DEPTH_PARAM, next)
+                .build(),
+        )?;
+        response
+            .headers_mut()
+            .insert(STEP_DEPTH_HEADER, HeaderValue::from(next));
+        Ok(response)
+    }
+}
+
+/// The depth of `req`, or the `508 Loop Detected` response to send once it exceeds
+/// `max_depth`.
+#[allow(clippy::result_large_err)]
+pub fn redirect_chain_guard(req: &Request, max_depth: u32) -> Result<StepDepth, Response<Body>> {
+    let depth = StepDepth::of(req);
+    if depth.0 <= max_depth {
+        return Ok(depth);
+    }
+
+    let message = format!("step chain exceeded {} redirects", max_depth);
+    tracing::error!(depth = depth.0, "{}", message);
+    Err(http::loop_detected(serde_json::json!({ "error": message }))
+        .expect("static responses are valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redirect_chain_guard, StepDepth};
+    use lambda_http::http::{self, StatusCode};
+    use lambda_http::{Body, Request};
+
+    fn request(uri: &str) -> Request {
+        http::Request::builder().uri(uri).body(Body::Empty).unwrap()
+    }
+
+    #[test]
+    fn it_caps_redirect_chains() {
+        let depth = redirect_chain_guard(&request("/api/step/1"), 2).unwrap();
+        assert_eq!(depth, StepDepth(0));
+        let response = depth.redirect("/api/step/2?run=a#top").unwrap();
+        let location = response.headers()["location"].to_str().unwrap();
+        assert_eq!(location, "/api/step/2?run=a&step_depth=1#top");
+        assert_eq!(response.headers()["x-step-depth"], "1");
+
+        let next = StepDepth(2).redirect("/api/step/3?step_depth=2").unwrap();
+        assert_eq!(next.headers()["location"], "/api/step/3?step_depth=3");
+        let response = redirect_chain_guard(&request("/api/step/3?step_depth=3"), 2).unwrap_err();
+        assert_eq!(response.status(), StatusCode::LOOP_DETECTED);
+    }
+}
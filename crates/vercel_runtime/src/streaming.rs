@@ -0,0 +1,338 @@
+use crate::body::Body;
+use bytes::{Bytes, BytesMut};
+use futures_util::Future;
+use lambda_http::{http::HeaderMap, Error};
+use std::{
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{sync::mpsc, time::Sleep};
+
+/// Number of chunks that may be queued before `BodySender::send` waits for the client.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Creates a streaming [`Body`] together with the sender used to feed it.
+///
+/// Return the body from the handler and write to the sender from a spawned task; the response
+/// ends when the sender is dropped.
+///
+/// Chunks are sent as soon as they are written, see [`BodySender::set_flush_policy`].
+pub fn channel() -> (BodySender, Body) {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let policy = Arc::new(Mutex::new(FlushPolicy::Immediate));
+    let stream = ChannelStream {
+        rx,
+        policy: policy.clone(),
+        buf: BytesMut::new(),
+        deadline: None,
+        error: None,
+    };
+    (
+        BodySender {
+            tx,
+            policy,
+            headers: None,
+        },
+        Body::from_stream(stream),
+    )
+}
+
+/// When written chunks are passed on to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Every chunk goes out on its own, for interactive streams such as tokens or SSE.
+    Immediate,
+    /// Chunks are coalesced until `bytes` are pending or `interval` passed since the first of
+    /// them, trading latency for fewer, larger writes in bulk exports.
+    Batched { bytes: usize, interval: Duration },
+}
+
+impl FlushPolicy {
+    /// Batching used for bulk formats such as JSON text sequences.
+    pub const BATCHED: FlushPolicy = FlushPolicy::Batched {
+        bytes: 16 * 1024,
+        interval: Duration::from_millis(50),
+    };
+}
+
+/// Writing half of a streaming [`Body`].
+#[derive(Debug, Clone)]
+pub struct BodySender {
+    tx: mpsc::Sender<Result<Bytes, Error>>,
+    policy: Arc<Mutex<FlushPolicy>>,
+    headers: Option<Arc<Mutex<PendingHeaders>>>,
+}
+
+impl BodySender {
+    /// Shares headers set through [`BodySender::headers_mut`] with the response the body
+    /// belongs to, the returned value goes into its extensions.
+    pub(crate) fn attach_headers(&mut self) -> StreamHeaders {
+        let headers = Arc::new(Mutex::new(PendingHeaders::default()));
+        self.headers = Some(headers.clone());
+        StreamHeaders(headers)
+    }
+
+    /// Headers to add to the response, until the first chunk is written.
+    ///
+    /// Fails once a chunk was written or the response headers were sent, instead of
+    /// dropping the change.
+    pub(crate) fn headers_mut(&self) -> Result<HeadersMut<'_>, Error> {
+        let pending = self
+            .headers
+            .as_ref()
+            .ok_or_else(|| Error::from("streaming body is not attached to a response"))?
+            .lock()
+            .map_err(|_| Error::from("response headers are poisoned"))?;
+        match pending.locked {
+            Some(reason) => Err(Error::from(reason)),
+            None => Ok(HeadersMut(pending)),
+        }
+    }
+
+    fn lock_headers(&self) {
+        if let Some(Ok(mut pending)) = self.headers.as_ref().map(|headers| headers.lock()) {
+            pending.locked.get_or_insert(WRITTEN);
+        }
+    }
+
+    /// Changes how chunks are flushed, for every clone of this sender.
+    ///
+    /// Pending chunks are flushed with the next chunk when switching to
+    /// [`FlushPolicy::Immediate`].
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        if let Ok(mut current) = self.policy.lock() {
+            *current = policy;
+        }
+    }
+
+    /// Sends a chunk to the client, waiting while the channel is full.
+    pub async fn send(&mut self, chunk: impl Into<Bytes>) -> Result<(), Error> {
+        self.lock_headers();
+        self.tx
+            .send(Ok(chunk.into()))
+            .await
+            .map_err(|_| Error::from(BODY_DROPPED))
+    }
+
+    /// Like [`BodySender::send`] for synchronous code, blocking the current thread.
+    ///
+    /// Must not be called from async code, use it from `spawn_blocking` or plain threads.
+    pub fn blocking_send(&mut self, chunk: impl Into<Bytes>) -> Result<(), Error> {
+        self.lock_headers();
+        self.tx
+            .blocking_send(Ok(chunk.into()))
+            .map_err(|_| Error::from(BODY_DROPPED))
+    }
+
+    /// Terminates the stream with an error.
+    pub async fn abort(self, error: impl Into<Error>) {
+        // The receiver being gone means there is nobody left to notify
+        let _ = self.tx.send(Err(error.into())).await;
+    }
+
+    /// Whether the client side of the stream has gone away.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// The handler returning a different response drops the body just like a client going away.
+const BODY_DROPPED: &str = "streaming body was dropped, either the client went away or the \
+                            handler returned a different response";
+const WRITTEN: &str = "response headers are locked once the first chunk is written";
+const SENT: &str = "response headers were already sent";
+
+#[derive(Debug, Default)]
+struct PendingHeaders {
+    headers: HeaderMap,
+    /// Why the headers can no longer change
+    locked: Option<&'static str>,
+}
+
+/// Headers of a streaming response that can still change, see [`BodySender::headers_mut`].
+#[derive(Debug)]
+pub struct HeadersMut<'a>(MutexGuard<'a, PendingHeaders>);
+
+impl Deref for HeadersMut<'_> {
+    type Target = HeaderMap;
+
+    fn deref(&self) -> &HeaderMap {
+        &self.0.headers
+    }
+}
+
+impl DerefMut for HeadersMut<'_> {
+    fn deref_mut(&mut self) -> &mut HeaderMap {
+        &mut self.0.headers
+    }
+}
+
+/// Response extension carrying the headers set through [`BodySender::headers_mut`].
+#[derive(Debug, Clone)]
+pub(crate) struct StreamHeaders(Arc<Mutex<PendingHeaders>>);
+
+impl StreamHeaders {
+    /// Moves the pending headers into `headers`, replacing those with the same name, and locks
+    /// them for good.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        let Ok(mut pending) = self.0.lock() else {
+            return;
+        };
+        pending.locked = Some(SENT);
+        let mut current = None;
+        for (name, value) in std::mem::take(&mut pending.headers) {
+            if let Some(name) = name {
+                headers.remove(&name);
+                current = Some(name);
+            }
+            if let Some(name) = &current {
+                headers.append(name.clone(), value);
+            }
+        }
+    }
+}
+
+struct ChannelStream {
+    rx: mpsc::Receiver<Result<Bytes, Error>>,
+    policy: Arc<Mutex<FlushPolicy>>,
+    buf: BytesMut,
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// Received while chunks were pending, sent right after them
+    error: Option<Error>,
+}
+
+impl ChannelStream {
+    fn flush(&mut self) -> Poll<Option<Result<Bytes, Error>>> {
+        self.deadline = None;
+        Poll::Ready(Some(Ok(self.buf.split().freeze())))
+    }
+}
+
+impl futures_util::Stream for ChannelStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(error) = self.error.take() {
+            return Poll::Ready(Some(Err(error)));
+        }
+
+        loop {
+            let policy = self
+                .policy
+                .lock()
+                .map_or(FlushPolicy::Immediate, |policy| *policy);
+            match self.rx.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => match policy {
+                    FlushPolicy::Immediate if self.buf.is_empty() => {
+                        return Poll::Ready(Some(Ok(chunk)))
+                    }
+                    FlushPolicy::Immediate => {
+                        self.buf.extend_from_slice(&chunk);
+                        return self.flush();
+                    }
+                    FlushPolicy::Batched { bytes, interval } => {
+                        self.buf.extend_from_slice(&chunk);
+                        if self.buf.len() >= bytes {
+                            return self.flush();
+                        }
+                        if self.deadline.is_none() {
+                            self.deadline = Some(Box::pin(tokio::time::sleep(interval)));
+                        }
+                    }
+                },
+                Poll::Ready(Some(Err(error))) if !self.buf.is_empty() => {
+                    self.error = Some(error);
+                    return self.flush();
+                }
+                Poll::Ready(None) if !self.buf.is_empty() => return self.flush(),
+                Poll::Ready(end) => return Poll::Ready(end),
+                Poll::Pending => {
+                    let elapsed = match &mut self.deadline {
+                        Some(deadline) => deadline.as_mut().poll(cx).is_ready(),
+                        None => false,
+                    };
+                    return match elapsed {
+                        true => self.flush(),
+                        false => Poll::Pending,
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, FlushPolicy};
+    use crate::Body;
+    use futures_util::StreamExt;
+    use lambda_http::http::HeaderMap;
+    use std::time::Duration;
+
+    async fn next_chunk(body: &mut Body) -> Option<String> {
+        let Body::Stream(stream) = body else {
+            unreachable!()
+        };
+        let chunk = stream.next().await?.unwrap();
+        Some(String::from_utf8(chunk.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn it_flushes_chunks_by_policy() {
+        let (mut sender, mut body) = channel();
+        sender.send("a").await.unwrap();
+        sender.send("b").await.unwrap();
+        assert_eq!(next_chunk(&mut body).await.as_deref(), Some("a"));
+        assert_eq!(next_chunk(&mut body).await.as_deref(), Some("b"));
+
+        sender.set_flush_policy(FlushPolicy::Batched {
+            bytes: 4,
+            interval: Duration::from_millis(10),
+        });
+        for chunk in ["cc", "dd", "e"] {
+            sender.send(chunk).await.unwrap();
+        }
+        // Full batches go out right away, the rest once the interval elapsed
+        assert_eq!(next_chunk(&mut body).await.as_deref(), Some("ccdd"));
+        assert_eq!(next_chunk(&mut body).await.as_deref(), Some("e"));
+
+        sender.send("f").await.unwrap();
+        drop(sender);
+        assert_eq!(next_chunk(&mut body).await.as_deref(), Some("f"));
+        assert_eq!(next_chunk(&mut body).await, None);
+    }
+
+    #[tokio::test]
+    async fn it_locks_headers_after_the_first_write() {
+        let (mut sender, _body) = channel();
+        assert!(sender.headers_mut().is_err());
+
+        let attached = sender.attach_headers();
+        sender
+            .headers_mut()
+            .unwrap()
+            .insert("x-step", "1".parse().unwrap());
+        sender.send("a").await.unwrap();
+        let error = sender.headers_mut().unwrap_err();
+        assert!(error.to_string().contains("first chunk"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-step", "0".parse().unwrap());
+        attached.apply(&mut headers);
+        assert_eq!(headers["x-step"], "1");
+        assert!(sender.headers_mut().is_err());
+    }
+
+    #[tokio::test]
+    async fn it_fails_writes_once_the_body_is_dropped() {
+        let (mut sender, body) = channel();
+        // E.g. the handler fell back to a buffered response
+        drop(body);
+        let error = sender.send("a").await.unwrap_err();
+        assert!(error.to_string().contains("different response"));
+        assert!(sender.is_closed());
+    }
+}
@@ -0,0 +1,141 @@
+//! [`Server-Timing`](https://www.w3.org/TR/server-timing/) entries collected during an
+//! invocation.
+//!
+//! Wrap each unit of work, e.g. every workflow node, in a [`timer`] and the entries show up
+//! in the timing tab of the browser devtools:
+//!
+//! ```no_run
+//! # async fn execute_single_node(_: usize) {}
+//! # async fn step(node_id: usize) {
+//! let timer = vercel_runtime::timing::timer(format!("node-{}", node_id));
+//! execute_single_node(node_id).await;
+//! timer.stop();
+//! # }
+//! ```
+//!
+//! The runtime sends everything recorded by the time the handler returns as one
+//! `Server-Timing` header, next to any the handler set itself. Headers of a streaming body go
+//! out before the first chunk and Lambda response streaming has no trailers, so entries
+//! recorded while a body streams are not sent.
+use lambda_http::http::{HeaderMap, HeaderValue};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+static RECORDED: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// Starts timing `name`, recorded when the timer is stopped or dropped.
+pub fn timer(name: impl Into<String>) -> Timer {
+    Timer {
+        name: Some(name.into()),
+        started: Instant::now(),
+    }
+}
+
+/// Records a `name;dur=...` entry measured elsewhere.
+pub fn record(name: impl Into<String>, duration: Duration) {
+    if let Ok(mut recorded) = RECORDED.lock() {
+        recorded.push((name.into(), duration));
+    }
+}
+
+/// A running [`timer`].
+#[derive(Debug)]
+#[must_use = "the timer records when it is dropped"]
+pub struct Timer {
+    name: Option<String>,
+    started: Instant,
+}
+
+impl Timer {
+    /// Records the entry now instead of when the timer goes out of scope.
+    pub fn stop(mut self) -> Duration {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Duration {
+        let elapsed = self.started.elapsed();
+        if let Some(name) = self.name.take() {
+            record(name, elapsed);
+        }
+        elapsed
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Forgets the entries of the previous invocation.
+pub(crate) fn reset() {
+    if let Ok(mut recorded) = RECORDED.lock() {
+        recorded.clear();
+    }
+}
+
+/// Appends the entries recorded so far to `headers`.
+pub(crate) fn apply(headers: &mut HeaderMap) {
+    let recorded = match RECORDED.lock() {
+        Ok(mut recorded) => std::mem::take(&mut *recorded),
+        Err(_) => return,
+    };
+    if let Some(value) = header_value(&recorded) {
+        headers.append(SERVER_TIMING_HEADER, value);
+    }
+}
+
+fn header_value(recorded: &[(String, Duration)]) -> Option<HeaderValue> {
+    if recorded.is_empty() {
+        return None;
+    }
+    let entries: Vec<String> = recorded
+        .iter()
+        .map(|(name, duration)| {
+            format!(
+                "{};dur={:.1}",
+                metric_name(name),
+                duration.as_secs_f64() * 1000.0
+            )
+        })
+        .collect();
+    HeaderValue::try_from(entries.join(", ")).ok()
+}
+
+/// Metric names are tokens, anything else is replaced with `-`.
+fn metric_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c,
+            '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' | '-' | '.' | '^' | '_' | '`' | '|'
+            | '~' => c,
+            _ => '-',
+        })
+        .collect();
+    match name.is_empty() {
+        true => "-".to_owned(),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::header_value;
+    use std::time::Duration;
+
+    #[test]
+    fn it_formats_server_timing_entries() {
+        let recorded = vec![
+            ("node-1".to_owned(), Duration::from_micros(12_340)),
+            ("node 2/llm".to_owned(), Duration::from_millis(950)),
+        ];
+        assert_eq!(
+            header_value(&recorded).unwrap(),
+            "node-1;dur=12.3, node-2-llm;dur=950.0"
+        );
+        assert!(header_value(&[]).is_none());
+    }
+}
@@ -0,0 +1,217 @@
+//! [W3C Trace Context](https://www.w3.org/TR/trace-context/) propagation.
+//!
+//! Every decoded request carries a [`TraceContext`] that continues the `traceparent` sent by
+//! the caller, or starts a new trace when there is none. The handler runs inside an
+//! `invocation` span tagged with its ids, and outgoing requests join the trace through
+//! [`TraceContext::inject`] (or `inject_trace` with the `reqwest` feature).
+use crate::utils::random_u64;
+use lambda_http::http::{HeaderMap, HeaderValue};
+use std::sync::RwLock;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+const FLAG_SAMPLED: u8 = 0x01;
+
+static CURRENT: RwLock<Option<TraceContext>> = RwLock::new(None);
+
+/// Trace position of the current invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits shared by every span of the trace.
+    pub trace_id: String,
+    /// Span id of this invocation, sent as the parent of outgoing requests.
+    pub span_id: String,
+    /// Span id of the caller, `None` when this invocation started the trace.
+    pub parent_id: Option<String>,
+    pub flags: u8,
+    /// Vendor specific `tracestate`, forwarded untouched.
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Continues the trace described by `traceparent`/`tracestate`, or starts a new one.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_traceparent);
+
+        let Some((trace_id, parent_id, flags)) = parent else {
+            return TraceContext::new_root();
+        };
+
+        let tracestate = headers
+            .get_all(TRACESTATE_HEADER)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        TraceContext {
+            trace_id,
+            span_id: random_span_id(),
+            parent_id: Some(parent_id),
+            flags,
+            tracestate: Some(tracestate).filter(|s| !s.is_empty()),
+        }
+    }
+
+    /// A sampled trace without a parent.
+    pub fn new_root() -> Self {
+        TraceContext {
+            trace_id: format!("{:016x}{:016x}", random_u64(), random_u64()),
+            span_id: random_span_id(),
+            parent_id: None,
+            flags: FLAG_SAMPLED,
+            tracestate: None,
+        }
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+
+    /// The `traceparent` value for requests made by this invocation.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+
+    /// Sets `traceparent` and `tracestate` on an outgoing request.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        // Both values only ever contain visible ASCII
+        if let Ok(value) = HeaderValue::try_from(self.traceparent()) {
+            headers.insert(TRACEPARENT_HEADER, value);
+        }
+        match self
+            .tracestate
+            .as_deref()
+            .and_then(|s| HeaderValue::try_from(s).ok())
+        {
+            Some(value) => headers.insert(TRACESTATE_HEADER, value),
+            None => headers.remove(TRACESTATE_HEADER),
+        };
+    }
+
+    pub(crate) fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "invocation",
+            trace_id = %self.trace_id,
+            span_id = %self.span_id,
+            parent_id = self.parent_id.as_deref(),
+        )
+    }
+}
+
+/// Trace context of the invocation being processed.
+///
+/// Functions handle one invocation at a time, so this is also correct inside spawned tasks.
+pub fn current() -> Option<TraceContext> {
+    CURRENT.read().ok().and_then(|c| c.clone())
+}
+
+pub(crate) fn set_current(context: TraceContext) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(context);
+    }
+}
+
+/// Adds the trace headers of the current invocation to a `reqwest` request.
+#[cfg(feature = "reqwest")]
+pub fn inject_trace(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let Some(context) = current() else {
+        return builder;
+    };
+    let mut headers = HeaderMap::new();
+    context.inject(&mut headers);
+    builder.headers(headers)
+}
+
+/// Parses `version-trace_id-parent_id-flags`, rejecting the invalid all-zero ids.
+fn parse_traceparent(value: &str) -> Option<(String, String, u8)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+
+    let is_hex = |s: &str, len: usize| {
+        s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    // Later versions may append fields, version 00 has exactly four
+    if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    Some((trace_id.to_owned(), parent_id.to_owned(), flags))
+}
+
+fn random_span_id() -> String {
+    format!("{:016x}", random_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_traceparent, TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+    use lambda_http::http::HeaderMap;
+
+    const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn it_parses_traceparent() {
+        let (trace_id, parent_id, flags) = parse_traceparent(PARENT).unwrap();
+        assert_eq!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent_id, "00f067aa0ba902b7");
+        assert_eq!(flags, 1);
+
+        assert!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-x")
+                .is_some()
+        );
+        assert!(parse_traceparent(&format!("{}-x", PARENT)).is_none());
+        assert!(
+            parse_traceparent("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none()
+        );
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none()
+        );
+        assert!(
+            parse_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_none()
+        );
+    }
+
+    #[test]
+    fn it_continues_incoming_traces() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT_HEADER, PARENT.parse().unwrap());
+        headers.insert(TRACESTATE_HEADER, "congo=t61rcWkgMzE".parse().unwrap());
+
+        let context = TraceContext::from_headers(&headers);
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.parent_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_ne!(context.span_id, "00f067aa0ba902b7");
+
+        let mut outgoing = HeaderMap::new();
+        context.inject(&mut outgoing);
+        let sent = TraceContext::from_headers(&outgoing);
+        assert_eq!(sent.trace_id, context.trace_id);
+        assert_eq!(sent.parent_id.as_deref(), Some(context.span_id.as_str()));
+        assert_eq!(sent.tracestate.as_deref(), Some("congo=t61rcWkgMzE"));
+    }
+
+    #[test]
+    fn it_starts_new_traces() {
+        let context = TraceContext::from_headers(&HeaderMap::new());
+        assert_eq!(context.trace_id.len(), 32);
+        assert_eq!(context.parent_id, None);
+        assert!(context.is_sampled());
+        assert_ne!(context.trace_id, TraceContext::new_root().trace_id);
+    }
+}
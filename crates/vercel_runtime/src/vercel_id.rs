@@ -0,0 +1,94 @@
+//! Parsing the `x-vercel-id` header Vercel adds to every request.
+//!
+//! The value lists the regions the request went through followed by a request id, e.g.
+//! `fra1::iad1::7xq2k-1700000000000-3f9c1a2b4d5e`: received at the `fra1` edge and run by a
+//! function in `iad1`.
+use lambda_http::http::HeaderMap;
+use std::{fmt, str::FromStr};
+
+pub const X_VERCEL_ID: &str = "x-vercel-id";
+
+/// A parsed `x-vercel-id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VercelId {
+    regions: Vec<String>,
+    id: String,
+}
+
+impl VercelId {
+    /// Parses `x-vercel-id`, `None` when it is absent or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers.get(X_VERCEL_ID)?.to_str().ok()?.parse().ok()
+    }
+
+    /// Every region the request passed through, edge first.
+    pub fn regions(&self) -> &[String] {
+        &self.regions
+    }
+
+    /// The edge region that received the request.
+    pub fn region(&self) -> &str {
+        &self.regions[0]
+    }
+
+    /// The last region of the chain, where the function handling the request runs.
+    pub fn function_region(&self) -> &str {
+        &self.regions[self.regions.len() - 1]
+    }
+
+    /// The request id after the regions.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl FromStr for VercelId {
+    type Err = ();
+
+    /// Needs at least one region before the id, and no empty parts.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = value.trim().split("::").collect();
+        let id = parts.pop().ok_or(())?;
+        if parts.is_empty() || id.is_empty() || parts.iter().any(|part| part.is_empty()) {
+            return Err(());
+        }
+        Ok(VercelId {
+            regions: parts.into_iter().map(str::to_owned).collect(),
+            id: id.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for VercelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in &self.regions {
+            write!(f, "{}::", region)?;
+        }
+        f.write_str(&self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VercelId;
+
+    #[test]
+    fn it_splits_regions_and_id() {
+        let id: VercelId = "fra1::iad1::7xq2k-1700000000000-3f9c1a2b4d5e"
+            .parse()
+            .unwrap();
+        assert_eq!(id.regions(), ["fra1", "iad1"]);
+        assert_eq!(id.region(), "fra1");
+        assert_eq!(id.function_region(), "iad1");
+        assert_eq!(id.id(), "7xq2k-1700000000000-3f9c1a2b4d5e");
+        assert_eq!(
+            id.to_string(),
+            "fra1::iad1::7xq2k-1700000000000-3f9c1a2b4d5e"
+        );
+
+        let single: VercelId = "dev1::abc".parse().unwrap();
+        assert_eq!(single.function_region(), "dev1");
+        assert!("abc".parse::<VercelId>().is_err());
+        assert!("fra1::::abc".parse::<VercelId>().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Signature verification for webhooks of common providers.
+//!
+//! Each verifier checks the provider specific signature over the raw request body and returns
+//! the deserialized payload. Stripe and Svix sign a timestamp along with the body, which is
+//! checked against `tolerance` to reject replayed deliveries. GitHub does not sign a
+//! timestamp, so replays can only be detected through the `X-GitHub-Delivery` id.
+use crate::{runtime, utils::constant_time_eq};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use lambda_http::http::HeaderMap;
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+use std::{
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Timestamp tolerance recommended by Stripe and Svix.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a webhook delivery was rejected.
+#[derive(Debug)]
+pub enum WebhookError {
+    /// A header required by the provider's scheme is absent or unreadable.
+    MissingHeader(&'static str),
+    InvalidSignature,
+    /// The signed timestamp is outside of the tolerance.
+    Expired,
+    /// The signature matched, but the body is not the expected JSON.
+    InvalidPayload(serde_json::Error),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::MissingHeader(name) => write!(f, "missing {} header", name),
+            WebhookError::InvalidSignature => f.write_str("invalid webhook signature"),
+            WebhookError::Expired => f.write_str("webhook timestamp outside of tolerance"),
+            WebhookError::InvalidPayload(e) => write!(f, "invalid webhook payload: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+/// Verifies `X-Hub-Signature-256: sha256=<hex>`.
+pub fn verify_github<T: DeserializeOwned>(
+    secret: &[u8],
+    body: &[u8],
+    headers: &HeaderMap,
+) -> Result<T, WebhookError> {
+    const HEADER: &str = "x-hub-signature-256";
+    let signature = header(headers, HEADER)?
+        .strip_prefix("sha256=")
+        .ok_or(WebhookError::InvalidSignature)?;
+
+    let expected = hex(&hmac(secret, &[body]));
+    if !constant_time_eq(signature.as_bytes(), expected.as_bytes()) {
+        return Err(WebhookError::InvalidSignature);
+    }
+    parse(body)
+}
+
+/// Verifies `Stripe-Signature: t=<timestamp>,v1=<hex>`, accepting any of several `v1` entries
+/// (sent while the secret is rolled).
+pub fn verify_stripe<T: DeserializeOwned>(
+    secret: &[u8],
+    body: &[u8],
+    headers: &HeaderMap,
+    tolerance: Duration,
+) -> Result<T, WebhookError> {
+    const HEADER: &str = "stripe-signature";
+    let value = header(headers, HEADER)?;
+
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for pair in value.split(',') {
+        match pair.trim().split_once('=') {
+            Some(("t", t)) => timestamp = Some(t),
+            Some(("v1", v)) => signatures.push(v),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or(WebhookError::MissingHeader(HEADER))?;
+
+    let expected = hex(&hmac(secret, &[timestamp.as_bytes(), b".", body]));
+    if !signatures
+        .iter()
+        .any(|s| constant_time_eq(s.as_bytes(), expected.as_bytes()))
+    {
+        return Err(WebhookError::InvalidSignature);
+    }
+    check_freshness(timestamp, tolerance)?;
+    parse(body)
+}
+
+/// Verifies the `svix-id`, `svix-timestamp` and `svix-signature` headers.
+///
+/// `secret` is the endpoint secret as shown by Svix, with or without its `whsec_` prefix.
+pub fn verify_svix<T: DeserializeOwned>(
+    secret: &str,
+    body: &[u8],
+    headers: &HeaderMap,
+    tolerance: Duration,
+) -> Result<T, WebhookError> {
+    let id = header(headers, "svix-id")?;
+    let timestamp = header(headers, "svix-timestamp")?;
+    let signatures = header(headers, "svix-signature")?;
+
+    let key = STANDARD
+        .decode(secret.strip_prefix("whsec_").unwrap_or(secret))
+        .map_err(|_| WebhookError::InvalidSignature)?;
+    let expected = STANDARD.encode(hmac(
+        &key,
+        &[id.as_bytes(), b".", timestamp.as_bytes(), b".", body],
+    ));
+
+    // Space separated `v1,<base64>` entries
+    if !signatures
+        .split(' ')
+        .filter_map(|s| s.strip_prefix("v1,"))
+        .any(|s| constant_time_eq(s.as_bytes(), expected.as_bytes()))
+    {
+        return Err(WebhookError::InvalidSignature);
+    }
+    check_freshness(timestamp, tolerance)?;
+    parse(body)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &'static str) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(WebhookError::MissingHeader(name))
+}
+
+fn hmac(secret: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn check_freshness(timestamp: &str, tolerance: Duration) -> Result<(), WebhookError> {
+    let timestamp: u64 = timestamp.parse().map_err(|_| WebhookError::Expired)?;
+    let now = runtime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    match now.abs_diff(timestamp) <= tolerance.as_secs() {
+        true => Ok(()),
+        false => Err(WebhookError::Expired),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, WebhookError> {
+    serde_json::from_slice(body).map_err(WebhookError::InvalidPayload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DEFAULT_TOLERANCE;
+    use super::{hex, hmac, verify_github, verify_stripe, verify_svix, WebhookError};
+    use lambda_http::http::HeaderMap;
+    use serde_json::Value;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const BODY: &[u8] = br#"{"action":"opened"}"#;
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn it_verifies_github_signatures() {
+        let signature = format!("sha256={}", hex(&hmac(b"secret", &[BODY])));
+        let valid = headers(&[("x-hub-signature-256", signature)]);
+        let payload: Value = verify_github(b"secret", BODY, &valid).unwrap();
+        assert_eq!(payload["action"], "opened");
+
+        assert!(matches!(
+            verify_github::<Value>(b"other", BODY, &valid),
+            Err(WebhookError::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify_github::<Value>(b"secret", BODY, &HeaderMap::new()),
+            Err(WebhookError::MissingHeader(_))
+        ));
+    }
+
+    #[test]
+    fn it_verifies_stripe_signatures() {
+        let sign = |t: u64| {
+            let t = t.to_string();
+            let v1 = hex(&hmac(b"whsec", &[t.as_bytes(), b".", BODY]));
+            headers(&[("stripe-signature", format!("t={},v1=deadbeef,v1={}", t, v1))])
+        };
+
+        assert!(verify_stripe::<Value>(b"whsec", BODY, &sign(now()), DEFAULT_TOLERANCE).is_ok());
+        assert!(matches!(
+            verify_stripe::<Value>(b"whsec", BODY, &sign(now() - 600), DEFAULT_TOLERANCE),
+            Err(WebhookError::Expired)
+        ));
+        assert!(matches!(
+            verify_stripe::<Value>(b"whsec", b"{}", &sign(now()), DEFAULT_TOLERANCE),
+            Err(WebhookError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn it_verifies_svix_signatures() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let t = now().to_string();
+        let signature = STANDARD.encode(hmac(b"key", &[b"msg_1.", t.as_bytes(), b".", BODY]));
+        let valid = headers(&[
+            ("svix-id", "msg_1".to_owned()),
+            ("svix-timestamp", t),
+            ("svix-signature", format!("v1,bogus v1,{}", signature)),
+        ]);
+        let secret = format!("whsec_{}", STANDARD.encode("key"));
+
+        assert!(verify_svix::<Value>(&secret, BODY, &valid, DEFAULT_TOLERANCE).is_ok());
+        assert!(matches!(
+            verify_svix::<Value>("whsec_b3RoZXI=", BODY, &valid, DEFAULT_TOLERANCE),
+            Err(WebhookError::InvalidSignature)
+        ));
+    }
+}
@@ -11,7 +11,7 @@ use vercel_runtime_router::{Route, Router};
 #[proc_macro_attribute]
 pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
     let args = parse_macro_input!(args as AttributeArgs);
-    let mut args_map: HashMap<String, String> = HashMap::new();
+    let mut args_map: HashMap<String, syn::LitStr> = HashMap::new();
 
     args.iter().for_each(|arg| {
         if let syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
@@ -21,13 +21,14 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
         })) = arg
         {
             if let Some(key) = path.get_ident() {
-                args_map.insert(key.to_string(), lit_str.value());
+                args_map.insert(key.to_string(), lit_str.clone());
             }
         }
     });
 
     let prefix = args_map
         .get("path")
+        .map(syn::LitStr::value)
         .map(|p| {
             if p.ends_with('/') {
                 p.to_owned()
@@ -50,6 +51,24 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
         })
         .unwrap_or("".to_string());
 
+    let trailing_slash = match args_map.get("trailing_slash") {
+        None => quote! { vercel_runtime::TrailingSlash::Ignore },
+        Some(lit_str) => match lit_str.value().as_str() {
+            "ignore" => quote! { vercel_runtime::TrailingSlash::Ignore },
+            "redirect" => quote! { vercel_runtime::TrailingSlash::Redirect },
+            "strict" => quote! { vercel_runtime::TrailingSlash::Strict },
+            other => {
+                let message = format!(
+                    "invalid trailing_slash `{}`, expected `ignore`, `redirect` or `strict`",
+                    other
+                );
+                return syn::Error::new_spanned(lit_str, message)
+                    .to_compile_error()
+                    .into();
+            }
+        },
+    };
+
     let glob_pattern = format!("{}api/**/*.rs", prefix);
     let main_path = PathBuf::from(format!("{}api/main.rs", prefix));
 
@@ -95,10 +114,13 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
         let Route {
             module_name,
             module_file,
+            path,
             ..
         } = r;
+        let template = format!("/{}", path);
         quote! {
             #module_file => {
+                req.extensions_mut().insert(vercel_runtime::MatchedRoute(#template));
                 return #module_name::handler(req).await;
             }
         }
@@ -114,20 +136,30 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
         #(#mod_statements)*
 
         #(#attrs)* #vis #sig {
+            let mut req = req;
             let raw_routes = vec![#(#router_path_tokens)*];
-            let router = Router::from(raw_routes);
+            let router = Router::from(raw_routes).with_trailing_slash(#trailing_slash);
 
             let request_uri = req.uri().path().to_string();
             let request_uri = request_uri.strip_prefix('/').unwrap_or(&request_uri);
 
-            match router.call(&request_uri) {
-                Some(route) => {
+            match router.resolve(&request_uri) {
+                vercel_runtime::RouteMatch::Found(route) => {
                     match route.module_file.as_str() {
                         #(#matches)*
                         _ => unreachable!()
                     }
                 }
-                None => unreachable!(),
+                vercel_runtime::RouteMatch::Redirect(path) => {
+                    let location = match req.uri().query() {
+                        Some(query) => format!("/{}?{}", path, query),
+                        None => format!("/{}", path),
+                    };
+                    return vercel_runtime::http::permanent_redirect(&location);
+                }
+                vercel_runtime::RouteMatch::NotFound => {
+                    return vercel_runtime::http::not_found("Not Found");
+                }
             }
         }
     }
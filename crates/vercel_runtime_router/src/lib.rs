@@ -167,8 +167,71 @@ impl From<&str> for Route {
     }
 }
 
+/// How paths ending in `/` are matched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// `api/step/3/` only matches routes that expect the slash, so file based routes 404.
+    Strict,
+    /// `api/step/3/` is answered with a redirect to `api/step/3`.
+    Redirect,
+    /// `api/step/3/` matches the same route as `api/step/3`.
+    #[default]
+    Ignore,
+}
+
+/// Outcome of [`Router::resolve`].
+#[derive(Debug)]
+pub enum RouteMatch<'a> {
+    Found(&'a Route),
+    /// The path only matches without its trailing slash, redirect to the canonical path.
+    Redirect(String),
+    NotFound,
+}
+
+impl Route {
+    /// Values of the dynamic segments of `req_path`, e.g. `[("id", "3")]` for `api/[id]`.
+    ///
+    /// Catch-all segments capture the rest of the path joined by `/`. A trailing slash is not
+    /// treated as an empty last segment.
+    pub fn params(&self, req_path: &str) -> Vec<(String, String)> {
+        let Some(ref segments) = self.segments else {
+            return Vec::new();
+        };
+        let req_path = req_path.trim_end_matches('/');
+        let path_segments = get_segments(req_path);
+
+        let mut params = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let Some(name) = segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) else {
+                continue;
+            };
+            // `[[...all]]` and `[...all]` take everything from here on
+            let catch_all = name
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .strip_prefix("...");
+            match catch_all {
+                Some(name) => {
+                    let rest = path_segments.get(i..).unwrap_or_default().join("/");
+                    if !rest.is_empty() {
+                        params.push((name.to_owned(), rest));
+                    }
+                    break;
+                }
+                None => {
+                    if let Some(value) = path_segments.get(i) {
+                        params.push((name.to_owned(), (*value).to_owned()));
+                    }
+                }
+            }
+        }
+        params
+    }
+}
+
 pub struct Router {
     pub routes: Vec<Route>,
+    pub trailing_slash: TrailingSlash,
 }
 
 impl Default for Router {
@@ -181,7 +244,10 @@ impl From<Vec<&str>> for Router {
     fn from(raw_paths: Vec<&str>) -> Self {
         let mut routes: Vec<Route> = raw_paths.into_iter().map(Route::from).collect();
         routes.sort();
-        Router { routes }
+        Router {
+            routes,
+            trailing_slash: TrailingSlash::default(),
+        }
     }
 }
 
@@ -197,10 +263,46 @@ impl Router {
             .collect::<Vec<_>>();
 
         routes.sort();
-        Router { routes }
+        Router {
+            routes,
+            trailing_slash: TrailingSlash::default(),
+        }
+    }
+
+    pub fn with_trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+        self
     }
 
     pub fn call(&self, req_path: &str) -> Option<&Route> {
+        match self.resolve(req_path) {
+            RouteMatch::Found(route) => Some(route),
+            RouteMatch::Redirect(_) | RouteMatch::NotFound => None,
+        }
+    }
+
+    /// Matches `req_path`, applying the [`TrailingSlash`] policy.
+    pub fn resolve(&self, req_path: &str) -> RouteMatch<'_> {
+        let trimmed = req_path.trim_end_matches('/');
+        let found = |path| {
+            self.find(path)
+                .map_or(RouteMatch::NotFound, RouteMatch::Found)
+        };
+        if trimmed.len() == req_path.len() || trimmed.is_empty() {
+            return found(req_path);
+        }
+
+        match self.trailing_slash {
+            TrailingSlash::Strict => found(req_path),
+            TrailingSlash::Ignore => found(trimmed),
+            TrailingSlash::Redirect => match self.find(trimmed) {
+                Some(_) => RouteMatch::Redirect(trimmed.to_owned()),
+                None => RouteMatch::NotFound,
+            },
+        }
+    }
+
+    fn find(&self, req_path: &str) -> Option<&Route> {
         // Check if there is an optional catch all route
         if let Some(optional_catch_all) = self.routes.iter().find(|r| {
             let dynamic_optional_catch_all_exp = Regex::new(r"\[{2}\.{3}\S+\]{2}").unwrap();
@@ -314,6 +416,50 @@ mod tests {
     }
 }
 
+#[cfg(test)]
+mod trailing_slash_tests {
+    use super::{Route, RouteMatch, Router, TrailingSlash};
+
+    fn router(trailing_slash: TrailingSlash) -> Router {
+        Router::from(vec!["api/step-v4/[step].rs", "api/files/[...path].rs"])
+            .with_trailing_slash(trailing_slash)
+    }
+
+    #[test]
+    fn it_applies_the_trailing_slash_policy() {
+        let ignore = router(TrailingSlash::Ignore);
+        assert_eq!(
+            ignore.call("api/step-v4/3/").unwrap().path,
+            "api/step-v4/[step]"
+        );
+        assert!(router(TrailingSlash::Strict)
+            .call("api/step-v4/3/")
+            .is_none());
+        assert!(matches!(
+            router(TrailingSlash::Redirect).resolve("api/step-v4/3/"),
+            RouteMatch::Redirect(p) if p == "api/step-v4/3"
+        ));
+        assert!(matches!(
+            router(TrailingSlash::Redirect).resolve("api/nope/"),
+            RouteMatch::NotFound
+        ));
+    }
+
+    #[test]
+    fn it_extracts_params() {
+        let route = Route::from("api/step-v4/[step].rs");
+        let params = vec![("step".to_owned(), "3".to_owned())];
+        assert_eq!(route.params("api/step-v4/3"), params);
+        assert_eq!(route.params("api/step-v4/3/"), params);
+
+        let route = Route::from("api/files/[...path].rs");
+        assert_eq!(
+            route.params("api/files/a/b/"),
+            vec![("path".to_owned(), "a/b".to_owned())]
+        );
+    }
+}
+
 #[cfg(test)]
 mod route_tests {
     use super::{Route, RouteKind};
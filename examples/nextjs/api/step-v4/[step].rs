@@ -1,7 +1,7 @@
 use serde_json::{json, Value};
 use http::Method;
 use std::convert::Infallible;
-use vercel_runtime::{run, Body, Error, Request, Response, StatusCode, wait_until};
+use vercel_runtime::{run, timing, Body, Error, Request, Response, StatusCode, wait_until};
 
 // Import your service functions (assumed available in Rust)
 use crate::db::mongo::store_execution_data_v2;
@@ -79,8 +79,13 @@ pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
         execute_nodes_group(node_or_group.clone(), trigger_output.clone(), webhook_body.clone())
             .await?
     } else {
-        execute_single_node(node_or_group.clone(), trigger_output.clone(), webhook_body.clone())
-            .await?
+        // Shows up as `node-<step>` in the Server-Timing of the response
+        let timer = timing::timer(format!("node-{}", step_index));
+        let result =
+            execute_single_node(node_or_group.clone(), trigger_output.clone(), webhook_body.clone())
+                .await?;
+        timer.stop();
+        result
     };
 
     // --- Streaming branch (SSE) ---
@@ -1,7 +1,7 @@
 use rand::seq::SliceRandom;
 
 pub fn choose_starter() -> String {
-    let pokemons = vec!["Bulbasaur", "Charmander", "Squirtle", "Pikachu"];
+    let pokemons = ["Bulbasaur", "Charmander", "Squirtle", "Pikachu"];
     let starter = pokemons.choose(&mut rand::thread_rng()).unwrap();
     starter.to_string()
 }